@@ -3,56 +3,61 @@ use std::iter::Iterator;
 use std::fmt;
 use std::cmp::min;
 
-use {SEEDS,START_SEEDS,PITS,FPITS};
-use utils::{binom,divmod,n_boards,enc_min};
+use MAX_FPITS;
+use utils::{Geometry,binom,binom_maxinv,divmod,n_boards,enc_min};
+use rules::{Rules,Ending};
+use game::Game;
 
 
 /// Representation of an awari board configuration.
 #[derive(Copy, Clone, Eq, PartialEq)]
-pub struct Awari([u8; FPITS]);
+pub struct Awari {
+    geo: Geometry,
+    pits: [u8; MAX_FPITS],
+}
 
 
-// use deref-coercion to provide all array (and slice) goodies on Awari
+// use deref-coercion to provide all slice goodies on Awari
 impl Deref for Awari {
-    type Target = [u8; FPITS];
+    type Target = [u8];
 
     #[inline]
-    fn deref(&self) -> &[u8; FPITS] {
-        let &Awari(ref v) = self;
-        return v;
+    fn deref(&self) -> &[u8] {
+        &self.pits[..self.geo.fpits()]
     }
 }
 
 
 impl DerefMut for Awari {
     #[inline]
-    fn deref_mut(&mut self) -> &mut [u8; FPITS] {
-        let &mut Awari(ref mut v) = self;
-        return v;
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let fpits = self.geo.fpits();
+        &mut self.pits[..fpits]
     }
 }
 
 
 impl fmt::Debug for Awari {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
         write!(f, "\n+")?;
-        for _ in 0..PITS {
+        for _ in 0..pits {
             write!(f, "--+")?;
         }
         write!(f, "\n|")?;
-        for i in (PITS..FPITS).rev() {
+        for i in (pits..fpits).rev() {
             write!(f, "{:2}|", self[i])?;
         }
         write!(f, "\n+")?;
-        for _ in 0..PITS {
+        for _ in 0..pits {
             write!(f, "--+")?;
         }
         write!(f, "\n|")?;
-        for i in 0..PITS {
+        for i in 0..pits {
             write!(f, "{:2}|", self[i])?;
         }
         write!(f, "\n+")?;
-        for _ in 0..PITS {
+        for _ in 0..pits {
             write!(f, "--+")?;
         }
         return Ok(());
@@ -60,44 +65,79 @@ impl fmt::Debug for Awari {
 }
 
 
-impl Default for Awari {
+impl Awari {
     /// Instanciate the canonical starting board configuration.
-    fn default() -> Self {
-        Awari([START_SEEDS as u8; FPITS])
+    pub fn new(geo: Geometry) -> Self {
+        let mut u = Awari::empty(geo);
+        for i in 0..geo.fpits() {
+            u[i] = geo.start_seeds() as u8;
+        }
+        return u;
+    }
+
+    /// Instanciate a board without any seed.
+    pub fn empty(geo: Geometry) -> Self {
+        Awari { geo: geo, pits: [0; MAX_FPITS] }
     }
-}
 
+    /// Return the geometry of the board.
+    #[inline]
+    pub fn geometry(&self) -> Geometry {
+        self.geo
+    }
 
-impl Awari {
     /// Iterate on every board configuration with a given number of seeds.
-    pub fn iter_config(n: usize) -> Iter {
-        let x = 1 << FPITS - 1;
-        return Iter { curr: x - 1,
+    pub fn iter_config(geo: Geometry, n: usize) -> Iter {
+        let x = 1 << geo.fpits() - 1;
+        return Iter { geo: geo,
+                      curr: x - 1,
                       last: (x - 1) << n,
                       mask: x << n,
-                      code: enc_min(n) - 1 }
+                      code: geo.layer_range(n).0.wrapping_sub(1),
+                      end: usize::max_value() }
+    }
+
+    /// Iterate on the board configurations with `n` seeds whose code is in
+    /// range `[start,end)`. This yields the same items as the corresponding
+    /// slice of `iter_config(n)`.
+    pub fn iter_config_range(geo: Geometry, n: usize, start: usize, end: usize) -> Iter {
+        let (lo, hi) = geo.layer_range(n);
+        assert!(lo <= start && start <= end && end <= hi, "code range out of layer");
+        let mut it = Awari::iter_config(geo, n);
+        it.end = end;
+        if start < end {
+            // the bit-vector has a one after the seeds of each pit
+            let xs = Awari::decode(geo, start).prefix();
+            it.curr = 0;
+            for i in 0..geo.fpits()-1 {
+                it.curr |= 1 << (xs[i] + i);
+            }
+            it.code = start.wrapping_sub(1);
+        } else {
+            it.curr = it.last + 1;
+        }
+        return it;
     }
 
     /// Return a compact encoding of an awari board as an integer.
     #[inline]
     pub fn encode(&self) -> usize {
-        let (mut g, mut c) = (0, 0);
-        let mut xs = [0; FPITS];
-        for i in 0..FPITS {
-            c += self[i] as usize;
-            xs[i] = c;
-            g += binom(i + 1, c + i);
+        let (pits, fpits, seeds) = (self.geo.pits, self.geo.fpits(), self.geo.seeds);
+        let mut xs = self.prefix();
+        let mut g = 0;
+        for i in 0..fpits {
+            g += binom(i + 1, xs[i] + i);
         }
 
         // configs with SEEDS-1 seeds are not accessible
-        if xs[FPITS-1] == SEEDS {
-            g -= n_boards(SEEDS - 1);
+        if xs[fpits-1] == seeds && !self.geo.full {
+            g -= n_boards(self.geo, seeds - 1);
         }
 
         let mut brk = 0;
-        let mut last = xs[PITS-1];
-        for i in PITS..FPITS {
-            if last >= xs[i] || xs[i] <= i - PITS {
+        let mut last = xs[pits-1];
+        for i in pits..fpits {
+            if last >= xs[i] || xs[i] <= i - pits {
                 xs[i] += 1;
                 brk = i;
             }
@@ -106,124 +146,166 @@ impl Awari {
 
         debug_assert!(brk > 0);
 
-        for i in brk..FPITS {
-            g -= binom(i + 1, xs[i] + PITS - 1);
+        for i in brk..fpits {
+            g -= binom(i + 1, xs[i] + pits - 1);
         }
         return g;
     }
 
-    /// Compute every legal predecessor that has the same score (only 0-valued
-    /// back-moves are allowed taken into account).
-    pub fn predecessors(&self) -> Vec<Self> {
-        let mut cpy = *self;
-        cpy.rotate();
-        
-        let mut v = Vec::new();
+    /// Compute the prefix sums of the pits.
+    #[inline]
+    fn prefix(&self) -> [usize; MAX_FPITS] {
+        let mut xs = [0; MAX_FPITS];
+        let mut c = 0;
+        for i in 0..self.geo.fpits() {
+            c += self[i] as usize;
+            xs[i] = c;
+        }
+        return xs;
+    }
 
-        if cpy[PITS..FPITS].iter().all(|&x| x == 0) {
-            return v;
+    /// Compute the board configuration whose code is `code` (inverse of
+    /// `encode`).
+    pub fn decode(geo: Geometry, code: usize) -> Self {
+        let (pits, fpits, seeds) = (geo.pits, geo.fpits(), geo.seeds);
+
+        if code == 0 {
+            return Awari::empty(geo);
         }
 
-        let mut cmin = [0; FPITS-1];
-        for i in 0..PITS {
-            if cpy[i] == 0 {
-                let mut m = cpy[i+1];
-                cmin[0] = m;
-                for r in 1..FPITS-1 {
-                    let x = cpy[(i+r+1) % FPITS];
-                    if m > x {
-                        m = x;
-                    }
-                    cmin[r] = m;
-                }
-                let last = cmin[FPITS-2]+1;
-                for r in 0..FPITS-1 {
-                    if ((i+r+1) % FPITS < PITS) || (cpy[(i+r+1) % FPITS] != 2 && cpy[(i+r+1) % FPITS] != 3) {
-                        for n in 0..min(cmin[r], last) {
-                            let mut s = cpy;
-                            s.unsow(i, r + 1, n);
-                            if cpy[PITS..FPITS].iter().any(|&x| x == 0) {
-                                v.push(s);
-                            }
-                        }
+        // find the number of seeds (skipping the SEEDS-1 layer)
+        let mut n = seeds;
+        if code < enc_min(geo, seeds) {
+            n -= if geo.full { 1 } else { 2 };
+            while enc_min(geo, n) > code {
+                n -= 1;
+            }
+        }
+
+        // unrank the combinatorial coefficients from the top, `full` is true
+        // while every opponent pit fixed so far is non-empty, in which case we
+        // have to skip the excluded configurations
+        let mut g = code - enc_min(geo, n);
+        let mut xs = [0; MAX_FPITS];
+        xs[fpits-1] = n + fpits - 1;
+        let mut full = true;
+        for i in (0..fpits-1).rev() {
+            if full && i + 1 >= pits {
+                // number of valid configurations with `xs[i] < c` is
+                // `binom(i+1, c) - binom(i+1, c-d)`, bisect on it
+                let d = i + 1 - pits;
+                let (mut a, mut b) = (i, xs[i+1]);
+                while b - a > 1 {
+                    let c = (a + b) / 2;
+                    if binom(i + 1, c) - binom(i + 1, c - d) <= g {
+                        a = c;
+                    } else {
+                        b = c;
                     }
                 }
+                g -= binom(i + 1, a) - binom(i + 1, a - d);
+                xs[i] = a;
+                full = xs[i+1] - a >= 2;
+            } else {
+                let (a, b) = binom_maxinv(geo, i + 1, g);
+                xs[i] = a;
+                g -= b;
             }
         }
-        return v;
+
+        let mut u = Awari::empty(geo);
+        u[0] = xs[0] as u8;
+        for i in 1..fpits {
+            u[i] = (xs[i] - xs[i-1] - 1) as u8;
+        }
+        return u;
     }
 
-    /*pub fn predecessors_nz(&self, n: u8) -> Vec<Self> {
-        debug_assert!(n > 0);
-        
+    /// Iterate on every legal predecessor that has the same score (only
+    /// 0-valued back-moves are taken into account).
+    #[inline]
+    pub fn predecessors<R: Rules>(&self, rules: R) -> Predecessors<R> {
+        Predecessors::new(*self, rules)
+    }
+
+    /// Compute every legal predecessor that reaches the current board with a
+    /// move capturing `k > 0` seeds.
+    pub fn predecessors_nz<R: Rules>(&self, k: u8, rules: R) -> Vec<Self> {
+        debug_assert!(k > 0);
+        let (pits, fpits, seeds) = (self.geo.pits, self.geo.fpits(), self.geo.seeds);
         let mut cpy = *self;
         cpy.rotate();
-        
+
         let mut v = Vec::new();
 
-        let mut i = PITS;
-        while i < FPITS && self[i] > 0 { i += 1; }
-        let a = i;
-        while i < FPITS && self[i] == 0 { i += 1; }
-        let b = i;
-
-        i = PITS - 1;
-        while i >= 0
-        let mut m = SEEDS;
-        while i >= 0 && self[i] > 0 {
-            m = min(m, self[i]);
-            i -= 1;
-        }
-
-        for c in (n+2)/3..1+min(n/2, (b - a) as u8) {
-            let mut x = (1 << (n - 2*c)) - 1;
-            let y = x << (3*c - n);
-            while x <= y {
-                let mut cpy = self;
-                for i in 0..c {
-                    cpy[a + i] = 2 + ((x >> i) & 1);
+        // the predecessors must have a code
+        let n = cpy.iter().fold(k as usize, |a, &x| a + x as usize);
+        if n > seeds || self.geo.skips(n) {
+            return v;
+        }
+
+        // the captured seeds come from a run `[a,b]` of empty opponent pits
+        // that all contained 2 or 3 seeds, the last one was sown in `b` or in
+        // `b+1` for a grand slam leaving the last pit
+        let k = k as usize;
+        for a in pits..fpits {
+            for b in a..fpits {
+                if cpy[b] != 0 {
+                    break;
+                }
+                let c = b + 1 - a;
+                if k < 2*c {
+                    break;
+                }
+                if k > 3*c {
+                    continue;
+                }
+                for x in 0..1usize << c {
+                    if x.count_ones() as usize != k - 2*c {
+                        continue;
+                    }
+                    let mut t = cpy;
+                    for l in 0..c {
+                        t[a+l] = 2 + ((x >> l) & 1) as u8;
+                    }
+                    t.unsow_to(b, k as u8, self, rules, &mut v);
+                    if a == pits && b + 1 < fpits && (t[b+1] == 2 || t[b+1] == 3) {
+                        t.unsow_to(b + 1, k as u8, self, rules, &mut v);
+                    }
                 }
-                let t = x | (x - 1);
-                x = (t+1) | (((!t & (t+1)) - 1) >> (x.trailing_zeros() + 1));
             }
         }
         return v;
-    }*/
+    }
 
-    /// Compute every legal successors configuration of the current
-    /// board together with the reward of the move.
+    /// Iterate on every legal successor configuration of the current board
+    /// together with the reward of the move.
     #[inline]
-    pub fn successors(&self) -> Vec<(Self, u8)> {
-        let mut v = Vec::new();
-        for i in 0..PITS {
-            if self.valid_sow(i) {
-                let mut s = *self;
-                let k = s.play(i);
-                v.push((s, k));
-            }
-        }
-        return v;
+    pub fn successors<R: Rules>(&self, rules: R) -> Successors<R> {
+        Successors { board: *self, rules: rules, i: 0 }
     }
 
     /// Rotate the board (simulating a player change).
     #[inline]
     fn rotate(&mut self) {
-        for i in 0..PITS {
-            self.swap(i, i + PITS);
+        let pits = self.geo.pits;
+        for i in 0..pits {
+            self.swap(i, i + pits);
         }
     }
 
     /// Test if `i` is a valid move.
-    fn valid_sow(&self, i: usize) -> bool {
+    fn valid_sow<R: Rules>(&self, i: usize, rules: R) -> bool {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
         let n = self[i];
-        if i >= PITS || n == 0 {
+        if i >= pits || n == 0 {
             return false;
         } else {
-            let (q, r) = divmod(n, (FPITS - 1) as u8);
-            let j = (i + r) % FPITS;
+            let (q, r) = divmod(n, (fpits - 1) as u8);
+            let j = (i + r) % fpits;
             if j < i { return true; }  // at least one everywhere, no capture
             let mut take = true;
-            for k in (PITS..FPITS).rev() {
+            for k in (pits..fpits).rev() {
                 if k > j {
                     if self[k] + q > 0 { return true; }
                 } else {
@@ -231,72 +313,368 @@ impl Awari {
                     if !take { return true; }
                 }
             }
-            return false;
+            // the opponent is left without seeds: either nothing was sown on
+            // his side or this is a grand slam
+            return j >= pits && rules.slam_legal();
         }
     }
 
+    /// Test if `i` is a valid move that captures nothing.
+    fn quiet<R: Rules>(&self, i: usize, rules: R) -> bool {
+        let mut s = *self;
+        self.valid_sow(i, rules) && s.play(i, rules) == 0
+    }
+
     /// Do the "sowing" part of the move, starting from pit `i`. Panics if
     /// this is not a valid move.
     fn sow(&mut self, i: usize) -> (usize, u8) {
-        debug_assert!(i < PITS, "pit index out of bounds");
+        let fpits = self.geo.fpits();
+        debug_assert!(i < self.geo.pits, "pit index out of bounds");
         debug_assert!(self[i] > 0, "no seeds in pit");
         let n = self[i];
         self[i] = 0;
-        let (q, r) = divmod(n, (FPITS - 1) as u8);
+        let (q, r) = divmod(n, (fpits - 1) as u8);
         for j in 1..r+1 {
-            self[(i+j) % FPITS] += q+1;
+            self[(i+j) % fpits] += q+1;
         }
-        for j in r+1..FPITS {
-            self[(i+j) % FPITS] += q;
+        for j in r+1..fpits {
+            self[(i+j) % fpits] += q;
         }
-        return ((i+r) % FPITS, q as u8);
+        return ((i+r) % fpits, q as u8);
     }
 
     /// Undo the sowing that was done starting from pit `i`, that contained
     /// `(FPITS-1)*n + r` seeds. Panics if this is not a valid predecessor.
     #[inline]
     fn unsow(&mut self, i: usize, r: usize, n: u8) {
+        let fpits = self.geo.fpits();
         for k in 0..r {
-            debug_assert!(self[(i+k+1) % FPITS] >= n + 1);
-            self[(i+k+1) % FPITS] -= n + 1;
+            debug_assert!(self[(i+k+1) % fpits] >= n + 1);
+            self[(i+k+1) % fpits] -= n + 1;
+        }
+        for k in r..fpits-1 {
+            debug_assert!(self[(i+k+1) % fpits] >= n);
+            self[(i+k+1) % fpits] -= n;
         }
-        for k in r..FPITS-1 {
-            debug_assert!(self[(i+k+1) % FPITS] >= n);
-            self[(i+k+1) % FPITS] -= n;
+        self[i] += ((fpits - 1) as u8)*n + r as u8;
+    }
+
+    /// Push to `v` every board that is sown into the current one with the
+    /// last seed in pit `j` and whose move results in `target` with `k`
+    /// captured seeds.
+    fn unsow_to<R: Rules>(&self, j: usize, k: u8, target: &Self, rules: R,
+                          v: &mut Vec<Self>) {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
+        for i in 0..pits {
+            if self[i] != 0 {
+                continue;
+            }
+            let r = j - i;
+            let mut bound = u8::max_value();
+            for l in 1..fpits {
+                let x = self[(i+l) % fpits];
+                bound = min(bound, if l <= r { x } else { x + 1 });
+            }
+            for n in 0..bound {
+                let mut s = *self;
+                s.unsow(i, r, n);
+                if s[pits..fpits].iter().all(|&x| x > 0) || !s.valid_sow(i, rules) {
+                    continue;
+                }
+                let mut w = s;
+                if w.play(i, rules) == k && w == *target {
+                    v.push(s);
+                }
+            }
         }
-        self[i] += ((FPITS - 1) as u8)*n + r as u8;
     }
 
     /// Finish the move that ended in pit `i` by collecting the needed seeds .
-    fn collect(&mut self, i: usize) -> u8 {
+    fn collect<R: Rules>(&mut self, i: usize, rules: R) -> u8 {
+        let pits = self.geo.pits;
         let mut j = i;
-        let mut n = 0;
-        while j >= PITS && (self[j] == 2 || self[j] == 3) {
-            n += self[j];
-            self[j] = 0;
+        while j >= pits && (self[j] == 2 || self[j] == 3) {
             j -= 1;
         }
+        if i >= pits && j < pits && self[i+1..].iter().all(|&x| x == 0) {
+            return rules.slam(self, i);
+        }
+        let mut n = 0;
+        for k in j+1..i+1 {
+            n += self[k];
+            self[k] = 0;
+        }
         return n;
     }
 
     /// Play the pit `i` and return the number of captured seeds. Panics if
     /// this is not a valid move (`self[i] == 0`).
-    fn play(&mut self, i: usize) -> u8 {
+    fn play<R: Rules>(&mut self, i: usize, rules: R) -> u8 {
         let (j, _) = self.sow(i);
-        let k = self.collect(j);
+        let k = self.collect(j, rules);
         self.rotate();
         return k;
     }
 }
 
 
+/// Iterator on the successors of a board, see `Awari::successors`.
+pub struct Successors<R: Rules> {
+    board: Awari,
+    rules: R,
+    i: usize,
+}
+
+
+impl<R: Rules> Iterator for Successors<R> {
+    type Item = (Awari, u8);
+
+    #[inline]
+    fn next(&mut self) -> Option<(Awari, u8)> {
+        while self.i < self.board.geo.pits {
+            let i = self.i;
+            self.i += 1;
+            if self.board.valid_sow(i, self.rules) {
+                let mut s = self.board;
+                let k = s.play(i, self.rules);
+                return Some((s, k));
+            }
+        }
+        return None;
+    }
+}
+
+
+/// Iterator on the 0-valued predecessors of a board, see
+/// `Awari::predecessors`. The back-moves are enumerated by starting pit `i`,
+/// sowing length `r + 1` and number of laps `n`.
+pub struct Predecessors<R: Rules> {
+    cpy: Awari,
+    rules: R,
+    cmin: [u8; MAX_FPITS],
+    last: u8,
+    i: usize,
+    r: usize,
+    n: u8,
+}
+
+
+impl<R: Rules> Predecessors<R> {
+    fn new(u: Awari, rules: R) -> Self {
+        let (pits, fpits) = (u.geo.pits, u.geo.fpits());
+        let mut cpy = u;
+        cpy.rotate();
+        let mut it = Predecessors { cpy: cpy, rules: rules, cmin: [0; MAX_FPITS],
+                                    last: 0, i: 0, r: 0, n: 0 };
+        if cpy[pits..fpits].iter().all(|&x| x == 0) {
+            it.i = pits;
+        } else {
+            it.seek();
+        }
+        return it;
+    }
+
+    /// Move to the next empty pit (starting from the current one) and compute
+    /// the bounds on the number of laps.
+    fn seek(&mut self) {
+        let (pits, fpits) = (self.cpy.geo.pits, self.cpy.geo.fpits());
+        while self.i < pits && self.cpy[self.i] != 0 {
+            self.i += 1;
+        }
+        if self.i == pits {
+            return;
+        }
+        let i = self.i;
+        let mut m = self.cpy[i+1];
+        self.cmin[0] = m;
+        for r in 1..fpits-1 {
+            let x = self.cpy[(i+r+1) % fpits];
+            if m > x {
+                m = x;
+            }
+            self.cmin[r] = m;
+        }
+        self.last = self.cmin[fpits-2] + 1;
+        self.r = 0;
+        self.n = 0;
+    }
+}
+
+
+impl<R: Rules> Iterator for Predecessors<R> {
+    type Item = Awari;
+
+    fn next(&mut self) -> Option<Awari> {
+        let (pits, fpits) = (self.cpy.geo.pits, self.cpy.geo.fpits());
+        while self.i < pits {
+            if self.r == fpits - 1 {
+                self.i += 1;
+                self.seek();
+                continue;
+            }
+            // ending on 2 or 3 seeds on the opponent side is a capture, unless
+            // it is a grand slam capturing nothing
+            let (i, r, n) = (self.i, self.r, self.n);
+            let j = (i+r+1) % fpits;
+            let slam = j >= pits && (self.cpy[j] == 2 || self.cpy[j] == 3);
+            if (slam && !self.rules.slam_legal()) || n >= min(self.cmin[r], self.last) {
+                self.r += 1;
+                self.n = 0;
+                continue;
+            }
+            self.n += 1;
+            let mut s = self.cpy;
+            s.unsow(i, r + 1, n);
+            if s[pits..fpits].iter().any(|&x| x == 0)
+                    && (!slam || s.quiet(i, self.rules)) {
+                return Some(s);
+            }
+        }
+        return None;
+    }
+}
+
+
+/// Awari with a given geometry and rule-set, as solved by the retrograde
+/// drivers. The layers are the numbers of seeds on the board.
+#[derive(Copy, Clone)]
+pub struct AwariGame<R: Rules> {
+    pub geo: Geometry,
+    pub rules: R,
+}
+
+
+impl<R: Rules> Game for AwariGame<R> {
+    type State = Awari;
+    type Iter = Iter;
+    type Successors = GameSuccessors<R>;
+    type Predecessors = GamePredecessors<R>;
+
+    fn name(&self) -> String {
+        format!("awari {} {} {} {}{}", self.geo.pits, self.geo.seeds,
+                self.rules.name(), self.rules.ending().name(),
+                if self.geo.full { " full" } else { "" })
+    }
+
+    fn len(&self) -> usize {
+        self.geo.nboards()
+    }
+
+    fn layers(&self) -> Vec<usize> {
+        // the SEEDS-1 layer is not accessible
+        (0..self.geo.seeds+1).filter(|&n| !self.geo.skips(n)).collect()
+    }
+
+    fn layer_range(&self, n: usize) -> (usize, usize) {
+        self.geo.layer_range(n)
+    }
+
+    fn iter_range(&self, n: usize, start: usize, end: usize) -> Iter {
+        Awari::iter_config_range(self.geo, n, start, end)
+    }
+
+    fn max_score(&self, n: usize) -> i8 {
+        n as i8
+    }
+
+    /// The players share the `n` seeds, unless an odd number of seeds is
+    /// split at the end.
+    fn same_parity(&self, n: usize) -> bool {
+        self.rules.ending() != Ending::Split || n % 2 == 0
+    }
+
+    #[inline]
+    fn encode(&self, u: &Awari) -> usize {
+        u.encode()
+    }
+
+    /// The boards of the SEEDS-1 layer and those without an empty opponent
+    /// pit have no code.
+    fn has_code(&self, u: &Awari) -> bool {
+        let n = u.iter().map(|&x| x as usize).sum::<usize>();
+        n <= self.geo.seeds && !self.geo.skips(n)
+            && u[self.geo.pits..].iter().any(|&x| x == 0)
+    }
+
+    fn start(&self) -> Awari {
+        Awari::new(self.geo)
+    }
+
+    #[inline]
+    fn successors(&self, u: &Awari) -> GameSuccessors<R> {
+        GameSuccessors { it: u.successors(self.rules) }
+    }
+
+    fn play(&self, u: &Awari, i: usize) -> Option<(Awari, u8, usize, bool)> {
+        if i >= self.geo.pits || !u.valid_sow(i, self.rules) {
+            return None;
+        }
+        let mut v = *u;
+        let k = v.play(i, self.rules);
+        Some((v, k, v.encode(), false))
+    }
+
+    #[inline]
+    fn predecessors(&self, u: &Awari) -> GamePredecessors<R> {
+        GamePredecessors { it: u.predecessors(self.rules) }
+    }
+
+    fn terminal_score(&self, u: &Awari) -> i8 {
+        self.rules.terminal_score(u)
+    }
+}
+
+
+/// Successors of a board with their code and pit, see `Game::successors`.
+pub struct GameSuccessors<R: Rules> {
+    it: Successors<R>,
+}
+
+
+impl<R: Rules> Iterator for GameSuccessors<R> {
+    type Item = (Awari, u8, usize, bool, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(Awari, u8, usize, bool, usize)> {
+        match self.it.next() {
+            // the players always alternate, and the pit was just played
+            Some((v, k)) => Some((v, k, v.encode(), false, self.it.i - 1)),
+            None => None,
+        }
+    }
+}
+
+
+/// Predecessors of a board with their code and pit, see
+/// `Game::predecessors`.
+pub struct GamePredecessors<R: Rules> {
+    it: Predecessors<R>,
+}
+
+
+impl<R: Rules> Iterator for GamePredecessors<R> {
+    type Item = (Awari, usize, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(Awari, usize, usize)> {
+        match self.it.next() {
+            // the back-move starts from the pit played
+            Some(v) => Some((v, v.encode(), self.it.i)),
+            None => None,
+        }
+    }
+}
+
+
 /// Iterator for awari board configurations with a given number of seeds.
 /// This is really fast thanks to bitwise tricks.
 pub struct Iter {
+    geo: Geometry,
     curr: usize,
     last: usize,
     mask: usize,
-    code: usize
+    code: usize,
+    end: usize,
 }
 
 
@@ -305,14 +683,15 @@ impl Iterator for Iter {
 
     #[inline]
     fn next(&mut self) -> Option<(usize, Awari)> {
-        if self.curr > self.last {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
+        if self.curr > self.last || self.code.wrapping_add(1) >= self.end {
             return None;
         } else {
             // extract the board
             let mut x = self.curr | self.mask;
-            let mut s: Awari = Default::default();
+            let mut s = Awari::empty(self.geo);
 
-            for i in 0..FPITS {
+            for i in 0..fpits {
                 let tz = x.trailing_zeros();
                 s[i] = tz as u8;
                 x >>= tz + 1;
@@ -323,10 +702,10 @@ impl Iterator for Iter {
             let t = c | (c - 1);
             self.curr = (t+1) | (((!t & (t+1)) - 1) >> (c.trailing_zeros() + 1));
 
-            if s[PITS..FPITS].iter().all(|&a| a > 0) {
+            if s[pits..fpits].iter().all(|&a| a > 0) {
                 return self.next();
             } else {
-                self.code += 1;
+                self.code = self.code.wrapping_add(1);
                 debug_assert!(self.code == s.encode());
                 return Some((self.code, s));
             }
@@ -340,83 +719,221 @@ mod tests {
     use rand::{Rng,thread_rng};
     use quickcheck::{Arbitrary,Gen,StdGen};
 
-    use {FPITS,SEEDS,NBOARDS};
-    use super::Awari;
+    use std::cmp::{min,max};
+    use utils::Geometry;
+    use game::Game;
+    use rules::{Rules,Forbidden,NoCapture,CaptureAll,AllButLast};
+    use super::{Awari,AwariGame};
+
+    const GEO: Geometry = Geometry { pits: 6, seeds: 24, full: false };
 
     
     impl Arbitrary for Awari {
         fn arbitrary<G: Gen>(g: &mut G) -> Awari {
-            let mut b: Awari = Default::default();
+            let fpits = GEO.fpits();
+            let mut b = Awari::empty(GEO);
 
             b[0] = 0;
-            let n = g.gen_range(0, SEEDS as u8) + 1;
-            for i in 1..FPITS {
+            let n = g.gen_range(0, GEO.seeds as u8) + 1;
+            for i in 1..fpits {
                 b[i] = g.gen_range(0, n);
             }
             b.sort();
-            for i in 0..FPITS-1 {
+            for i in 0..fpits-1 {
                 b[i] = b[i+1] - b[i];
             }
-            b[FPITS-1] = n - b[FPITS-1];
+            b[fpits-1] = n - b[fpits-1];
             return b;
         }
     }
 
+    /// Test if one of the opponent pits is empty, the other boards can't be
+    /// reached by a move.
+    fn opp_empty(u: &Awari) -> bool {
+        u[GEO.pits..].iter().any(|&x| x == 0)
+    }
+
+    /// Test if the board has a code (ie it is not in the SEEDS-1 layer and
+    /// one of the opponent pits is empty).
+    fn encodable(u: &Awari) -> bool {
+        let n = u.iter().map(|&x| x as usize).sum::<usize>();
+        !GEO.skips(n) && opp_empty(u)
+    }
+
+    fn arbitrary_encodable<G: Gen>(g: &mut G) -> Awari {
+        loop {
+            let u = Awari::arbitrary(g);
+            if encodable(&u) {
+                return u;
+            }
+        }
+    }
+
 
-    /*#[quickcheck]
+    #[quickcheck]
     fn coding_bijective(u: Awari) -> bool {
-        u == Awari::decode(u.encode())
-    }*/
+        !encodable(&u) || u == Awari::decode(GEO, u.encode())
+    }
+
+    #[test]
+    fn full_coding() {
+        let geo = Geometry::new_full(3, 9);
+        let mut next = 0;
+        for n in 0..geo.seeds+1 {
+            for (c, u) in Awari::iter_config(geo, n) {
+                assert_eq!(c, next);
+                assert_eq!(u.encode(), c);
+                assert!(Awari::decode(geo, c) == u);
+                next += 1;
+            }
+        }
+        assert_eq!(next, geo.nboards());
+    }
+
+    fn succ_in_pred<R: Rules>(u: Awari, rules: R) -> bool {
+        u.successors(rules)
+          .all(|(v, k)| k > 0 || v.predecessors(rules)
+                                   .any(|w| u == w ))
+    }
+
+    fn pred_in_succ<R: Rules>(u: Awari, rules: R) -> bool {
+        u.predecessors(rules)
+          .all(|v| v.successors(rules)
+                     .any(|(w, k)| k == 0 && u == w))
+    }
 
     #[quickcheck]
     fn all_succ_in_pred(u: Awari) -> bool {
-        u.successors()
-          .into_iter()
-          .all(|(v, k)| k > 0 || v.predecessors()
-                                   .into_iter()
-                                   .any(|w| u == w ))
+        !opp_empty(&u) || (succ_in_pred(u, Forbidden)
+                           && succ_in_pred(u, NoCapture)
+                           && succ_in_pred(u, CaptureAll)
+                           && succ_in_pred(u, AllButLast))
     }
 
     #[quickcheck]
     fn all_pred_in_succ(u: Awari) -> bool {
-        u.predecessors()
-          .into_iter()
-          .all(|v| v.successors()
-                     .into_iter()
-                     .any(|(w, _)| u == w))
+        pred_in_succ(u, Forbidden)
+          && pred_in_succ(u, NoCapture)
+          && pred_in_succ(u, CaptureAll)
+          && pred_in_succ(u, AllButLast)
+    }
+
+    fn succ_in_pred_nz<R: Rules>(u: Awari, rules: R) -> bool {
+        u.successors(rules)
+          .all(|(v, k)| k == 0 || v.predecessors_nz(k, rules)
+                                    .into_iter()
+                                    .any(|w| u == w))
+    }
+
+    fn pred_in_succ_nz<R: Rules>(u: Awari, rules: R) -> bool {
+        (1..GEO.seeds as u8 + 1).all(|k| {
+            u.predecessors_nz(k, rules)
+             .into_iter()
+             .all(|v| v.successors(rules)
+                        .any(|(w, l)| l == k && u == w))
+        })
+    }
+
+    #[quickcheck]
+    fn all_succ_in_pred_nz(u: Awari) -> bool {
+        !encodable(&u) || (succ_in_pred_nz(u, Forbidden)
+                           && succ_in_pred_nz(u, NoCapture)
+                           && succ_in_pred_nz(u, CaptureAll)
+                           && succ_in_pred_nz(u, AllButLast))
+    }
+
+    #[quickcheck]
+    fn all_pred_in_succ_nz(u: Awari) -> bool {
+        pred_in_succ_nz(u, Forbidden)
+          && pred_in_succ_nz(u, NoCapture)
+          && pred_in_succ_nz(u, CaptureAll)
+          && pred_in_succ_nz(u, AllButLast)
+    }
+
+    /// Boards without a code are never listed by `predecessors_nz`, nor by
+    /// `predecessors` when none of their opponent pits is empty, so their
+    /// moves are left out of `all_succ_in_pred` and `all_succ_in_pred_nz`.
+    fn not_in_pred<R: Rules>(u: Awari, rules: R) -> bool {
+        u.successors(rules).all(|(v, k)| {
+            if k == 0 {
+                opp_empty(&u) || v.predecessors(rules).all(|w| u != w)
+            } else {
+                v.predecessors_nz(k, rules).into_iter().all(|w| u != w)
+            }
+        })
+    }
+
+    #[quickcheck]
+    fn unencodable_not_in_pred(u: Awari) -> bool {
+        encodable(&u) || (not_in_pred(u, Forbidden)
+                          && not_in_pred(u, NoCapture)
+                          && not_in_pred(u, CaptureAll)
+                          && not_in_pred(u, AllButLast))
+    }
+
+    /// The moves given with the successors and predecessors of the game lead
+    /// to the same boards.
+    fn game_moves<R: Rules>(u: Awari, rules: R) -> bool {
+        let game = AwariGame { geo: GEO, rules: rules };
+        game.successors(&u).all(|(v, k, d, again, i)| {
+            game.play(&u, i) == Some((v, k, d, again))
+        }) && game.predecessors(&u).all(|(w, d, i)| {
+            d == w.encode() && game.play(&w, i).map(|x| x.0) == Some(u)
+        })
+    }
+
+    #[quickcheck]
+    fn all_game_moves(u: Awari) -> bool {
+        game_moves(u, Forbidden)
+          && game_moves(u, NoCapture)
+          && game_moves(u, CaptureAll)
+          && game_moves(u, AllButLast)
+    }
+
+    #[quickcheck]
+    fn iter_config_range_slice(n: usize, a: usize, b: usize) -> bool {
+        let geo = Geometry::new(4, 12);
+        let n = n % (geo.seeds + 1);
+        if geo.skips(n) { return true; }
+        let (lo, hi) = geo.layer_range(n);
+        let len = hi - lo;
+        let (a, b) = (a % (len + 1), b % (len + 1));
+        let (a, b) = (min(a, b), max(a, b));
+        Awari::iter_config_range(geo, n, lo + a, lo + b)
+              .eq(Awari::iter_config(geo, n).skip(a).take(b - a))
     }
 
     #[bench]
     fn bench_encode_100(b: &mut Bencher) {
         let mut gen = StdGen::new(thread_rng(), 100);
-        let board = Awari::arbitrary(&mut gen);
+        let board = arbitrary_encodable(&mut gen);
         b.iter(|| { for _ in 0..100 { black_box(board.encode()); } });
     }
 
-    /*#[bench]
+    #[bench]
     fn bench_decode_100(b: &mut Bencher) {
         let mut rng = thread_rng();
-        let n = rng.gen_range(0, NBOARDS);
-        b.iter(|| { for _ in 0..100 { black_box(Awari::decode(n)); } });
-    }*/
+        let n = rng.gen_range(0, GEO.nboards());
+        b.iter(|| { for _ in 0..100 { black_box(Awari::decode(GEO, n)); } });
+    }
 
     #[bench]
     fn bench_successors_100(b: &mut Bencher) {
         let mut gen = StdGen::new(thread_rng(), 100);
-        let board = Awari::arbitrary(&mut gen);
-        b.iter(|| { for _ in 0..100 { black_box(board.successors()); } });
+        let board = arbitrary_encodable(&mut gen);
+        b.iter(|| { for _ in 0..100 { black_box(board.successors(Forbidden).count()); } });
     }
 
     #[bench]
     fn bench_predecessors_100(b: &mut Bencher) {
         let mut gen = StdGen::new(thread_rng(), 100);
-        let board = Awari::arbitrary(&mut gen);
-        b.iter(|| { for _ in 0..100 { black_box(board.predecessors()); } });
+        let board = arbitrary_encodable(&mut gen);
+        b.iter(|| { for _ in 0..100 { black_box(board.predecessors(Forbidden).count()); } });
     }
 
     #[bench]
     fn bench_iterconfig_100(b: &mut Bencher) {
-        let mut iter = Awari::iter_config(24);
+        let mut iter = Awari::iter_config(GEO, 24);
         b.iter(|| { for _ in 0..100 { black_box(iter.next()); } });
     }
 }
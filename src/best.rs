@@ -0,0 +1,134 @@
+use std::path::Path;
+use std::convert::AsRef;
+use std::fs::File;
+use std::io::{self,BufReader,BufWriter};
+
+use MAX_PITS;
+use game::Game;
+use packed::PackedVec;
+
+
+/// Optimal move of every board, 3 bits per board (enough for `MAX_PITS`
+/// pits), indexed by the codes of the boards.
+///
+/// It is filled by `ra::Driver` when its `best` field is set: the recorded
+/// move of a board always leads to a board that got its final score before
+/// it, so following the recorded moves cannot cycle unless the score is 0.
+/// It is only kept in memory during the run (the CLI saves it at the end with
+/// `--best`), so layers done before resuming or extending a run don't have
+/// their moves, and the CLI refuses `--best` for those runs.
+pub struct BestMoves {
+    data: PackedVec,
+}
+
+impl BestMoves {
+    pub fn new(len: usize) -> Self {
+        BestMoves { data: PackedVec::new(3, len) }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Recorded pit of the board whose code is `code`.
+    #[inline]
+    pub fn get(&self, code: usize) -> usize {
+        self.data.get(code) as usize
+    }
+
+    /// Record the move (pit `i`) of the board whose code is `c`.
+    #[inline]
+    pub fn record(&mut self, c: usize, i: usize) {
+        debug_assert!(i < MAX_PITS);
+        self.data.set(c, i as u8);
+    }
+
+    /// Optimal move of the state `u` of `game`, `None` if the game is over.
+    pub fn best_move<G: Game>(&self, game: &G, u: &G::State) -> Option<usize> {
+        let i = self.get(game.encode(u));
+        game.play(u, i).map(|_| i)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.data.write_to(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = PackedVec::read_from(&mut BufReader::new(File::open(path)?))?;
+        if data.bits() != 3 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a move table"));
+        }
+        Ok(BestMoves { data: data })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use kalah::KalahGame;
+    use rules::{Forbidden,AllButLast};
+    use ra::{Driver,NaiveRAM,Solver};
+    use super::BestMoves;
+
+    /// Check that the recorded moves reach the optimal scores and that
+    /// following them converts every non-zero score.
+    fn check<G: Game>(game: G) {
+        let log = Logger::root(Discard, o!());
+        let mut driver = Driver { best: Some(BestMoves::new(game.len())),
+                                  ..Driver::new(game, NaiveRAM::new(game.len(), log.clone()), log) };
+        driver.run();
+        let (table, best) = (&driver.table, driver.best.as_ref().unwrap());
+
+        for n in game.layers() {
+            let (lo, hi) = game.layer_range(n);
+            for (c, u) in game.iter_layer(n) {
+                let (mut u, mut x) = (u, table[c].val);
+                let i = match best.best_move(&game, &u) {
+                    Some(i) => i,
+                    None => {
+                        assert!(game.successors(&u).next().is_none());
+                        continue;
+                    },
+                };
+                let (_, k, d, again) = game.play(&u, i).unwrap();
+                let y = table[d].val;
+                assert_eq!(x, if again { k as i8 + y } else { k as i8 - y });
+
+                // stay in the layer for at most as many plies as there are
+                // boards in it
+                let mut plies = 0;
+                while x != 0 {
+                    let (v, k, d, again) = game.play(&u, best.best_move(&game, &u).unwrap())
+                                               .unwrap();
+                    if k > 0 || again {
+                        break;
+                    }
+                    u = v;
+                    x = table[d].val;
+                    plies += 1;
+                    assert!(plies <= hi - lo, "cycle from {}", c);
+                    if game.successors(&u).next().is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn best_moves_awari() {
+        check(AwariGame { geo: Geometry::new(3, 10), rules: Forbidden });
+        check(AwariGame { geo: Geometry::new(4, 8), rules: AllButLast });
+    }
+
+    #[test]
+    fn best_moves_kalah() {
+        check(KalahGame { geo: Geometry::new(3, 10) });
+    }
+}
@@ -1,21 +1,20 @@
-#![feature(placement_in_syntax,box_heap)]
-
-use std::boxed::{Box,HEAP};
-use std::default::Default;
+use std::env;
 
 extern crate awari;
 
-use awari::{NBOARDS,START_SEEDS,FPITS};
+use awari::Geometry;
 use awari::awari::Awari;
+use awari::rules::Forbidden;
+use awari::bitvec::BitVec;
 
-fn explore(start: Awari) -> Box<[bool; NBOARDS]> {
-    let mut tbl = HEAP <- [false; NBOARDS];
+fn explore(start: Awari) -> BitVec {
+    let mut tbl = BitVec::new(start.geometry().nboards());
     let mut stack = vec![start];
     while let Some(u) = stack.pop() {
-        for (v, _) in u.successors() {
+        for (v, _) in u.successors(Forbidden) {
             let id = v.encode();
-            if !tbl[id] {
-                tbl[id] = true;
+            if !tbl.get(id) {
+                tbl.set(id, true);
                 stack.push(v);
             }
         }
@@ -24,13 +23,13 @@ fn explore(start: Awari) -> Box<[bool; NBOARDS]> {
 }
 
 fn main() {
+    let pits = env::args().nth(1).unwrap().parse::<usize>().unwrap();
+    let seeds = env::args().nth(2).unwrap().parse::<usize>().unwrap();
+    let geo = Geometry::new(pits, seeds);
+
     println!("blabla");
-    let mut start: Awari = Default::default();
-    for i in 0..FPITS {
-        start[i] = START_SEEDS as u8;
-    }
-    let tbl = explore(start);
-    for i in 0..NBOARDS {
-        println!("{}: {}", i, tbl[i]);
+    let tbl = explore(Awari::new(geo));
+    for i in 0..geo.nboards() {
+        println!("{}: {}", i, tbl.get(i));
     }
 }
@@ -7,46 +7,48 @@ use std::env;
 
 use rand::{Rng,thread_rng};
 
-use awari::{FPITS,SEEDS};
+use awari::Geometry;
 use awari::awari::Awari;
+use awari::rules::Forbidden;
 use awari::utils::n_boards;
 
 
 
-fn rand_awari<R: Rng>(g: &mut R, n: u8) -> Awari {
-    let mut b: Awari = Default::default();
+fn rand_awari<R: Rng>(g: &mut R, geo: Geometry, n: u8) -> Awari {
+    let fpits = geo.fpits();
+    let mut b = Awari::empty(geo);
 
     b[0] = 0;
-    for i in 1..FPITS {
+    for i in 1..fpits {
         b[i] = g.gen_range(0, n);
     }
     b.sort();
-    for i in 0..FPITS-1 {
+    for i in 0..fpits-1 {
         b[i] = b[i+1] - b[i];
     }
-    b[FPITS-1] = n - b[FPITS-1];
+    b[fpits-1] = n - b[fpits-1];
     return b;
 }
 
 
-fn mean<R,F,T>(gen: &mut R, seeds: u8, n: usize, mut f: F) -> f64
+fn mean<R,F,T>(gen: &mut R, geo: Geometry, seeds: u8, n: usize, mut f: F) -> f64
     where R: Rng,
           F: FnMut(Awari) -> T,
           T: Into<f64> {
     let mut tot = 0.0;
     for _ in 0..n {
-        tot += f(rand_awari(gen, seeds)).into();
+        tot += f(rand_awari(gen, geo, seeds)).into();
     }
     return tot / (n as f64);
         
 }
 
-fn mean_gain<R: Rng>(gen: &mut R, seeds: u8, n: usize) -> f64 {
+fn mean_gain<R: Rng>(gen: &mut R, geo: Geometry, seeds: u8, n: usize) -> f64 {
     let mut x = 0;
     let mut cnt = 0usize;
     for _ in 0..n {
-        let u = rand_awari(gen, seeds);
-        for (_, k) in u.successors() {
+        let u = rand_awari(gen, geo, seeds);
+        for (_, k) in u.successors(Forbidden) {
             cnt += 1;
             x += k as usize;
         }
@@ -57,6 +59,9 @@ fn mean_gain<R: Rng>(gen: &mut R, seeds: u8, n: usize) -> f64 {
 
 fn main() {
     let niters = env::args().nth(1).unwrap().parse::<usize>().unwrap();
+    let pits = env::args().nth(2).unwrap().parse::<usize>().unwrap();
+    let seeds = env::args().nth(3).unwrap().parse::<usize>().unwrap();
+    let geo = Geometry::new(pits, seeds);
     println!("{} samples per measure", niters);
     println!("====== ====== ====== ======");
     println!("  gain  moves  nz-mv  bk-mv");
@@ -64,16 +69,16 @@ fn main() {
     let mut gen = thread_rng();
     let mut cost = 0.0;
 
-    for n in 1..SEEDS+1 {
+    for n in 1..seeds+1 {
         let s = n as u8;
-        let g = mean_gain(&mut gen, s, niters);
-        let mv = mean(&mut gen, s, niters, |u| u.successors().len() as f64);
-        let nzm = mean(&mut gen, s, niters, |u| u.successors().into_iter()
+        let g = mean_gain(&mut gen, geo, s, niters);
+        let mv = mean(&mut gen, geo, s, niters, |u| u.successors(Forbidden).count() as f64);
+        let nzm = mean(&mut gen, geo, s, niters, |u| u.successors(Forbidden)
                 .filter(|&(_, k)| k > 0).count() as f64);
-        let bm = mean(&mut gen, s, niters, |u| u.predecessors().len() as f64);
+        let bm = mean(&mut gen, geo, s, niters, |u| u.predecessors(Forbidden).count() as f64);
 
-        if n != SEEDS-1 {
-            cost += (2.0 + ((n+1)/2) as f64 + bm + nzm) * (n_boards(n) as f64);
+        if !geo.skips(n) {
+            cost += (2.0 + ((n+1)/2) as f64 + bm + nzm) * (n_boards(geo, n) as f64);
         }
         println!("{:6.4} {:6.4} {:6.4} {:6.4}", g, mv, nzm, bm);
     }
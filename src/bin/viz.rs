@@ -2,9 +2,11 @@ use std::fs::File;
 use std::io::{BufWriter,Result,Write};
 
 extern crate awari;
+use awari::Geometry;
 use awari::awari::Awari;
+use awari::rules::Forbidden;
 
-pub fn write_dot(out: &str, n: usize) -> Result<()> {
+pub fn write_dot(out: &str, geo: Geometry, n: usize) -> Result<()> {
     let mut stream = BufWriter::new(File::create(out)?);
     write!(&mut stream, "digraph foo {{\n")?;
     write!(&mut stream, "rankdir=\"LR\";\n")?;
@@ -12,14 +14,14 @@ pub fn write_dot(out: &str, n: usize) -> Result<()> {
     for i in (0..n).rev() {
         //write!(&mut stream, "subgraph cluster_{} {{\n", i)?;
         //write!(&mut stream, "label=\"{} seeds\";\n", i)?;
-        for u in Awari::iter_config(i) {
-            write!(&mut stream, "{id} [style=filled,label={id},color=\"/spectral11/{}\"];\n", i+1, id=u.encode())?;
+        for (id, _) in Awari::iter_config(geo, i) {
+            write!(&mut stream, "{id} [style=filled,label={id},color=\"/spectral11/{}\"];\n", i+1, id=id)?;
         }
         //write!(&mut stream, "}}\n")?;
     }
     for i in 0..n {
-        for u in Awari::iter_config(i) {
-            for (v, k) in u.successors() {
+        for (_, u) in Awari::iter_config(geo, i) {
+            for (v, k) in u.successors(Forbidden) {
                 if k > 0 {
                     write!(&mut stream, "{} -> {} [color=red,label={}];\n", u.encode(), v.encode(), k)?;
                 } else {
@@ -0,0 +1,24 @@
+use std::env;
+use std::mem;
+use std::fs::metadata;
+use std::path::Path;
+
+#[macro_use] extern crate slog;
+extern crate awari;
+
+use slog::{Logger,Discard};
+
+use awari::ra::{State,MMaped};
+use awari::wdl::WdlTable;
+
+/// Derive the win/draw/loss table `<dir>/wdl` from the finished score table
+/// `<dir>/table_mmap`.
+fn main() {
+    let dir = env::args().nth(1).expect("usage: wdl <dir>");
+    let wd = Path::new(&dir);
+    let size = metadata(wd.join("table_mmap")).unwrap().len() as usize;
+    let len = size / mem::size_of::<State>();
+
+    let table = MMaped::new(wd, len, Logger::root(Discard, o!())).unwrap();
+    WdlTable::from_scores(&table, len).save(wd.join("wdl")).unwrap();
+}
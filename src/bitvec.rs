@@ -0,0 +1,292 @@
+use std::path::Path;
+use std::convert::AsRef;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::io::{self,Read,Write,BufWriter};
+use std::ops::Deref;
+use std::{mem,ptr,slice};
+
+use libc;
+
+
+/// Plain vector of bits, to be turned into a `RankSelect` once filled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitVec {
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl BitVec {
+    /// Instanciate a vector of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        BitVec { len: len, words: vec![0; (len + 63) / 64] }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize) -> bool {
+        debug_assert!(i < self.len);
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, i: usize, x: bool) {
+        debug_assert!(i < self.len);
+        if x {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+}
+
+
+/// Words of a `RankSelect`, either owned or mapped from a file.
+enum Words {
+    Heap(Vec<u64>),
+    Mapped(*const u64, usize),
+}
+
+impl Deref for Words {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        match *self {
+            Words::Heap(ref v) => v,
+            Words::Mapped(p, n) => unsafe { slice::from_raw_parts(p, n) },
+        }
+    }
+}
+
+impl Drop for Words {
+    fn drop(&mut self) {
+        if let Words::Mapped(p, n) = *self {
+            unsafe {
+                libc::munmap(p as *mut libc::c_void, n * mem::size_of::<u64>());
+            }
+        }
+    }
+}
+
+// the mapping is read-only
+unsafe impl Send for Words {}
+unsafe impl Sync for Words {}
+
+
+/// Number of words per block of the rank index.
+const BLOCK: usize = 8;
+
+
+/// Immutable bit vector with a rank index: `rank` is a constant time
+/// operation and `select` a bisection on the blocks of `BLOCK` words.
+///
+/// Everything is stored in a single array of words: the length, the bits,
+/// then the number of ones before every block followed by the total number of
+/// ones. Written as little-endian words, it can be mapped directly from a file
+/// on little-endian machines (the index costs 1/8 of the bits).
+pub struct RankSelect {
+    data: Words,
+    /// number of words of bits
+    nw: usize,
+}
+
+impl RankSelect {
+    /// Build the rank index of `bits`.
+    pub fn new(bits: BitVec) -> Self {
+        let nw = bits.words.len();
+        let mut data = Vec::with_capacity(nw + nw / BLOCK + 3);
+        data.push(bits.len as u64);
+        data.extend_from_slice(&bits.words);
+        let mut r = 0;
+        for b in bits.words.chunks(BLOCK) {
+            data.push(r);
+            r += b.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+        }
+        data.push(r);
+        RankSelect { data: Words::Heap(data), nw: nw }
+    }
+
+    #[inline]
+    fn words(&self) -> &[u64] {
+        &self.data[1..1+self.nw]
+    }
+
+    #[inline]
+    fn blocks(&self) -> &[u64] {
+        &self.data[1+self.nw..]
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data[0] as usize
+    }
+
+    /// Number of ones.
+    #[inline]
+    pub fn ones(&self) -> usize {
+        self.data[self.data.len()-1] as usize
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize) -> bool {
+        debug_assert!(i < self.len());
+        self.words()[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// Number of ones before `i`.
+    #[inline]
+    pub fn rank(&self, i: usize) -> usize {
+        if i >= self.len() {
+            return self.ones();
+        }
+        let (words, w) = (self.words(), i / 64);
+        let mut r = self.blocks()[w / BLOCK];
+        for x in &words[w - w % BLOCK..w] {
+            r += x.count_ones() as u64;
+        }
+        r as usize + (words[w] & ((1 << (i % 64)) - 1)).count_ones() as usize
+    }
+
+    /// Position of the `j`-th one (`len` if there is none).
+    pub fn select(&self, j: usize) -> usize {
+        if j >= self.ones() {
+            return self.len();
+        }
+        // last block with at most `j` ones before it
+        let (words, blocks) = (self.words(), self.blocks());
+        let (mut a, mut b) = (0, blocks.len() - 1);
+        while b - a > 1 {
+            let m = (a + b) / 2;
+            if blocks[m] as usize <= j {
+                a = m;
+            } else {
+                b = m;
+            }
+        }
+        let mut j = j - blocks[a] as usize;
+        let mut w = a * BLOCK;
+        while words[w].count_ones() as usize <= j {
+            j -= words[w].count_ones() as usize;
+            w += 1;
+        }
+        let mut x = words[w];
+        for _ in 0..j {
+            x &= x - 1;
+        }
+        64 * w + x.trailing_zeros() as usize
+    }
+
+    /// Serialize the vector and its index.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for &x in self.data.iter() {
+            let mut buf = [0; 8];
+            for i in 0..8 {
+                buf[i] = (x >> (8 * i)) as u8;
+            }
+            w.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a vector written by `write_to`.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = Vec::new();
+        r.read_to_end(&mut buf)?;
+        let data = buf.chunks(8)
+                      .map(|b| b.iter().rev().fold(0, |x, &y| (x << 8) | y as u64))
+                      .collect();
+        RankSelect::checked(Words::Heap(data))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(&mut BufWriter::new(File::create(path)?))
+    }
+
+    /// Map a file written by `save` in memory.
+    #[cfg(target_endian = "little")]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = File::open(path)?;
+        let size = f.metadata()?.len() as usize;
+        if size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty bit vector"));
+        }
+        let p = unsafe {
+            libc::mmap(ptr::null_mut(), size as libc::size_t, libc::PROT_READ,
+                       libc::MAP_SHARED, f.as_raw_fd(), 0)
+        };
+        if p == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        RankSelect::checked(Words::Mapped(p as *const u64, size / mem::size_of::<u64>()))
+    }
+
+    /// Check that the size of `data` matches its length.
+    fn checked(data: Words) -> io::Result<Self> {
+        let nw = data.get(0).map_or(0, |&n| (n as usize + 63) / 64);
+        if data.len() == 0 || data.len() != nw + (nw + BLOCK - 1) / BLOCK + 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad bit vector"));
+        }
+        Ok(RankSelect { data: data, nw: nw })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::remove_file;
+
+    use super::{BitVec,RankSelect};
+
+    fn build(xs: &[bool]) -> RankSelect {
+        let mut v = BitVec::new(xs.len());
+        for (i, &x) in xs.iter().enumerate() {
+            v.set(i, x);
+        }
+        RankSelect::new(v)
+    }
+
+    fn naive(v: &RankSelect, xs: &[bool]) -> bool {
+        let mut r = 0;
+        for (i, &x) in xs.iter().enumerate() {
+            if v.get(i) != x || v.rank(i) != r {
+                return false;
+            }
+            if x {
+                if v.select(r) != i {
+                    return false;
+                }
+                r += 1;
+            }
+        }
+        v.ones() == r && v.rank(xs.len()) == r && v.select(r) == xs.len()
+    }
+
+    #[quickcheck]
+    fn rank_select(xs: Vec<bool>, rep: usize) -> bool {
+        // long runs to get several blocks
+        let xs: Vec<bool> = xs.iter().flat_map(|&x| vec![x; rep % 300]).collect();
+        naive(&build(&xs), &xs)
+    }
+
+    #[quickcheck]
+    fn serialize(xs: Vec<bool>) -> bool {
+        let v = build(&xs);
+        let mut buf = Vec::new();
+        v.write_to(&mut buf).unwrap();
+        naive(&RankSelect::read_from(&mut &buf[..]).unwrap(), &xs)
+    }
+
+    #[test]
+    fn mmap() {
+        let xs: Vec<bool> = (0..10000).map(|i| i % 7 == 0 || i % 11 == 0).collect();
+        let path = env::temp_dir().join("awari-bitvec-test");
+        build(&xs).save(&path).unwrap();
+        assert!(naive(&RankSelect::open(&path).unwrap(), &xs));
+        remove_file(&path).unwrap();
+    }
+}
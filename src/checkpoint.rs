@@ -0,0 +1,156 @@
+use std::path::{Path,PathBuf};
+use std::convert::AsRef;
+use std::fs::{File,rename};
+use std::io::{self,Read,Write};
+
+use game::Game;
+
+
+/// Progress of a solving run.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Progress {
+    /// Last layer whose scores are final.
+    pub done: usize,
+    /// Whether the layers of the smaller table were moved (see
+    /// `ra::Driver::extend`).
+    pub moved: bool,
+    /// Layer being solved and number of its saturation steps completed.
+    pub step: Option<(usize, usize)>,
+}
+
+
+/// Manifest recording the progress of a run, stored in `<wd>/manifest` next
+/// to the table.
+///
+/// Solving a layer only writes the states of this layer, so a run can always
+/// restart from the beginning of the first layer that was not done. The
+/// current step is only recorded to follow the progress: the states written by
+/// an interrupted step are not consistent.
+pub struct Checkpoint {
+    path: PathBuf,
+    header: String,
+    done: usize,
+    moved: bool,
+    step: Option<(usize, usize)>,
+}
+
+
+impl Checkpoint {
+    pub fn new<T: AsRef<Path>, G: Game>(wd: T, game: &G) -> Self {
+        Checkpoint {
+            path: wd.as_ref().join("manifest"),
+            header: game.name(),
+            done: 0,
+            moved: false,
+            step: None,
+        }
+    }
+
+    /// Read the progress of a previous run, if any.
+    pub fn load(&mut self) -> io::Result<Option<Progress>> {
+        let mut buf = String::new();
+        match File::open(&self.path) {
+            Ok(mut f) => { f.read_to_string(&mut buf)?; },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "bad manifest");
+        let mut lines = buf.lines();
+        if lines.next() != Some(&self.header[..]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "manifest of another variant"));
+        }
+        let mut p = Progress { done: 0, moved: false, step: None };
+        for l in lines {
+            let xs = l.split(' ')
+                      .skip(1)
+                      .map(|x| x.parse().map_err(|_| invalid()))
+                      .collect::<io::Result<Vec<usize>>>()?;
+            match (l.split(' ').next(), xs.len()) {
+                (Some("done"), 1) => p.done = xs[0],
+                (Some("moved"), 0) => p.moved = true,
+                (Some("step"), 2) => p.step = Some((xs[0], xs[1])),
+                _ => return Err(invalid()),
+            }
+        }
+        self.done = p.done;
+        self.moved = p.moved;
+        self.step = p.step;
+        return Ok(Some(p));
+    }
+
+    /// Record that the layer `n` is done. The table must have been flushed.
+    pub fn layer_done(&mut self, n: usize) -> io::Result<()> {
+        self.done = n;
+        self.step = None;
+        self.save()
+    }
+
+    /// Record that `l` saturation steps of layer `n` are completed.
+    pub fn step_done(&mut self, n: usize, l: usize) -> io::Result<()> {
+        self.step = Some((n, l));
+        self.save()
+    }
+
+    /// Record that the layers of the smaller table were moved to their place
+    /// in the extended one. The table must have been flushed.
+    pub fn layers_moved(&mut self) -> io::Result<()> {
+        self.moved = true;
+        self.save()
+    }
+
+    /// Atomically replace the manifest.
+    fn save(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut f = File::create(&tmp)?;
+            writeln!(f, "{}", self.header)?;
+            writeln!(f, "done {}", self.done)?;
+            if self.moved {
+                writeln!(f, "moved")?;
+            }
+            if let Some((n, l)) = self.step {
+                writeln!(f, "step {} {}", n, l)?;
+            }
+            f.sync_all()?;
+        }
+        rename(tmp, &self.path)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{create_dir_all,remove_dir_all};
+
+    use utils::Geometry;
+    use awari::AwariGame;
+    use rules::{Forbidden,NoCapture};
+    use super::{Checkpoint,Progress};
+
+    #[test]
+    fn manifest_roundtrip() {
+        let wd = env::temp_dir().join("awari-manifest-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let geo = Geometry::new(3, 12);
+        let mut c = Checkpoint::new(&wd, &AwariGame { geo: geo, rules: Forbidden });
+        assert_eq!(c.load().unwrap(), None);
+        c.layer_done(4).unwrap();
+        c.step_done(5, 2).unwrap();
+
+        let mut d = Checkpoint::new(&wd, &AwariGame { geo: geo, rules: Forbidden });
+        assert_eq!(d.load().unwrap(),
+                   Some(Progress { done: 4, moved: false, step: Some((5, 2)) }));
+        d.layers_moved().unwrap();
+        assert_eq!(c.load().unwrap(),
+                   Some(Progress { done: 4, moved: true, step: Some((5, 2)) }));
+        c.layer_done(5).unwrap();
+        assert_eq!(d.load().unwrap(), Some(Progress { done: 5, moved: true, step: None }));
+        assert!(Checkpoint::new(&wd, &AwariGame { geo: geo, rules: NoCapture })
+                  .load().is_err());
+        remove_dir_all(&wd).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+use std::path::Path;
+use std::convert::AsRef;
+use std::fs::{File,rename,remove_file};
+use std::os::unix::io::AsRawFd;
+use std::io::{self,Write,Seek,SeekFrom,BufWriter};
+use std::cmp::min;
+use std::{ptr,slice};
+
+use libc;
+
+use game::Game;
+use ra::{State,Scores};
+use bitvec::RankSelect;
+use packed::{write_u64,read_u64};
+
+
+/// First bytes of a database file.
+pub const MAGIC: &'static [u8; 8] = b"AWARIDB\n";
+/// Version of the container format.
+pub const VERSION: u64 = 2;
+/// Version of the crate, the codes of the boards may change between versions.
+pub const CRATE_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+/// Value encoding: one signed byte per board, the final score for the player
+/// to move.
+pub const SCORE_I8: u64 = 1;
+
+/// Number of values converted at once when writing a database.
+const CHUNK: usize = 1 << 16;
+
+/// Initial state of the FNV-1a hash.
+const FNV_INIT: u64 = 0xcbf29ce484222325;
+
+
+/// Continue the FNV-1a hash `h` with `data`.
+fn fnv(h: u64, data: &[u8]) -> u64 {
+    data.iter().fold(h, |h, &x| (h ^ x as u64).wrapping_mul(0x100000001b3))
+}
+
+
+/// FNV-1a hash of `data`.
+fn checksum(data: &[u8]) -> u64 {
+    fnv(FNV_INIT, data)
+}
+
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_u64(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+
+/// Read a string written by `write_str` from the bytes `r` (the length is
+/// checked before the header checksum, so it can't be trusted).
+fn read_str(r: &mut &[u8]) -> io::Result<String> {
+    let n = read_u64(r)?;
+    if n > r.len() as u64 {
+        return Err(invalid("bad string"));
+    }
+    let (s, rest) = r.split_at(n as usize);
+    *r = rest;
+    String::from_utf8(s.to_vec()).map_err(|_| invalid("bad string"))
+}
+
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+
+/// Description of the table of a game: its name (which includes the geometry
+/// and the rules), the code range of every layer and the rank index of the
+/// states, if any (see `Game::index`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Layout {
+    pub game: String,
+    pub len: usize,
+    /// number, start and end of every layer, by increasing codes
+    pub layers: Vec<(usize, usize, usize)>,
+    /// serialized rank index (see `RankSelect::write_to`)
+    pub index: Option<Vec<u8>>,
+}
+
+impl Layout {
+    pub fn new<G: Game>(game: &G) -> Self {
+        let mut layers: Vec<_> = game.layers().into_iter().map(|n| {
+            let (a, b) = game.layer_range(n);
+            (n, a, b)
+        }).collect();
+        layers.sort_by_key(|l| l.1);
+        let index = game.index().map(|set| {
+            let mut buf = Vec::new();
+            set.write_to(&mut buf).unwrap();
+            buf
+        });
+        Layout { game: game.name(), len: game.len(), layers: layers, index: index }
+    }
+}
+
+
+/// Write the finished `table` of the game described by `layout` in the file
+/// `path` (replaced atomically).
+///
+/// The file starts with `MAGIC`, the format and crate versions, the name of
+/// the game, the value encoding, the number of boards, every layer with the
+/// checksum of its values and the size and checksum of the rank index (0 if
+/// there is none), followed by the checksum of this header. The rank index
+/// and the values come next, one byte per board in the order of the codes.
+/// Integers are 64-bit little-endian, strings are prefixed by their length.
+///
+/// The values are streamed from the table layer by layer, and the header is
+/// written again with the checksums once they are known.
+pub fn write<P, T>(layout: &Layout, table: &T, path: P) -> io::Result<()>
+        where P: AsRef<Path>, T: Scores {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    match write_file(layout, table, &tmp) {
+        Ok(()) => rename(tmp, path),
+        Err(e) => {
+            let _ = remove_file(&tmp);
+            Err(e)
+        },
+    }
+}
+
+
+fn write_file<T: Scores>(layout: &Layout, table: &T, path: &Path) -> io::Result<()> {
+    let index = layout.index.as_ref().map_or(&[][..], |b| &b[..]);
+
+    // the checksums of the layers and of the header are filled in at the end
+    let mut head = Vec::new();
+    head.extend_from_slice(MAGIC);
+    write_u64(&mut head, VERSION)?;
+    write_str(&mut head, CRATE_VERSION)?;
+    write_str(&mut head, &layout.game)?;
+    write_u64(&mut head, SCORE_I8)?;
+    write_u64(&mut head, layout.len as u64)?;
+    write_u64(&mut head, layout.layers.len() as u64)?;
+    let mut sums = Vec::with_capacity(layout.layers.len());
+    for &(n, a, b) in &layout.layers {
+        write_u64(&mut head, n as u64)?;
+        write_u64(&mut head, a as u64)?;
+        write_u64(&mut head, b as u64)?;
+        sums.push(head.len());
+        write_u64(&mut head, 0)?;
+    }
+    write_u64(&mut head, index.len() as u64)?;
+    write_u64(&mut head, checksum(index))?;
+    let end = head.len();
+    write_u64(&mut head, 0)?;
+
+    let mut f = BufWriter::new(File::create(path)?);
+    f.write_all(&head)?;
+    f.write_all(index)?;
+    let mut c = 0;
+    for (&(_, a, b), &o) in layout.layers.iter().zip(sums.iter()) {
+        // codes out of every layer, if any
+        write_scores(table, c, a, &mut f)?;
+        let h = write_scores(table, a, b, &mut f)?;
+        write_u64(&mut &mut head[o..o+8], h)?;
+        c = b;
+    }
+    write_scores(table, c, layout.len, &mut f)?;
+    let h = checksum(&head[..end]);
+    write_u64(&mut &mut head[end..], h)?;
+
+    let mut f = f.into_inner().map_err(|e| e.into_error())?;
+    f.seek(SeekFrom::Start(0))?;
+    f.write_all(&head)?;
+    f.sync_all()
+}
+
+
+/// Write the scores of the codes in `[a,b)` of `table` and return their
+/// checksum.
+fn write_scores<T: Scores, W: Write>(table: &T, a: usize, b: usize, w: &mut W)
+        -> io::Result<u64> {
+    let mut buf = vec![0; min(b.saturating_sub(a), CHUNK)];
+    let mut h = FNV_INIT;
+    let mut c = a;
+    while c < b {
+        let e = min(b, c + CHUNK);
+        for (x, i) in buf.iter_mut().zip(c..e) {
+            let s = table.state(i);
+            if s.nsuc.is_some() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "unstable state"));
+            }
+            *x = s.val as u8;
+        }
+        h = fnv(h, &buf[..e-c]);
+        w.write_all(&buf[..e-c])?;
+        c = e;
+    }
+    Ok(h)
+}
+
+
+/// Read-only mapping of a whole file.
+struct Mapped {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl Mapped {
+    fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = File::open(path)?;
+        let len = f.metadata()?.len() as usize;
+        // empty files can't be mapped
+        if len < MAGIC.len() {
+            return Err(invalid("not a database"));
+        }
+        let p = unsafe {
+            libc::mmap(ptr::null_mut(), len as libc::size_t, libc::PROT_READ,
+                       libc::MAP_SHARED, f.as_raw_fd(), 0)
+        };
+        if p == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapped { ptr: p as *const u8, len: len })
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+// the mapping is read-only
+unsafe impl Send for Mapped {}
+unsafe impl Sync for Mapped {}
+
+
+/// Scores of a database file mapped in memory, indexed by codes.
+pub struct Database {
+    layout: Layout,
+    map: Mapped,
+    /// offset of the values in the file
+    start: usize,
+}
+
+impl Database {
+    /// Map the database `path` of `game`, refusing files of other games (or
+    /// with another rank index), of another format or crate version, and
+    /// corrupted ones.
+    pub fn open<P: AsRef<Path>, G: Game>(path: P, game: &G) -> io::Result<Self> {
+        let map = Mapped::open(path)?;
+        let buf = map.bytes();
+        if &buf[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a database"));
+        }
+
+        let r = &mut &buf[MAGIC.len()..];
+        if read_u64(r)? != VERSION {
+            return Err(invalid("unsupported format version"));
+        }
+        if read_str(r)? != CRATE_VERSION {
+            return Err(invalid("database of another crate version"));
+        }
+        let layout = Layout::new(game);
+        if read_str(r)? != layout.game {
+            return Err(invalid("database of another game"));
+        }
+        if read_u64(r)? != SCORE_I8 {
+            return Err(invalid("unsupported value encoding"));
+        }
+        if read_u64(r)? != layout.len as u64 || read_u64(r)? != layout.layers.len() as u64 {
+            return Err(invalid("database of another layout"));
+        }
+        let mut sums = Vec::with_capacity(layout.layers.len());
+        for &(n, a, b) in &layout.layers {
+            if read_u64(r)? != n as u64 || read_u64(r)? != a as u64 || read_u64(r)? != b as u64 {
+                return Err(invalid("database of another layout"));
+            }
+            sums.push(read_u64(r)?);
+        }
+        let (nindex, index_sum) = (read_u64(r)? as usize, read_u64(r)?);
+        let end = buf.len() - r.len();
+        if read_u64(r)? != checksum(&buf[..end]) {
+            return Err(invalid("corrupted header"));
+        }
+
+        let start = buf.len() - r.len();
+        if r.len() < nindex || r.len() - nindex != layout.len {
+            return Err(invalid("bad database size"));
+        }
+        let (index, data) = r.split_at(nindex);
+        if checksum(index) != index_sum {
+            return Err(invalid("corrupted rank index"));
+        }
+        if index != layout.index.as_ref().map_or(&[][..], |b| &b[..]) {
+            return Err(invalid("database of another rank index"));
+        }
+        // the layers are sorted by codes, so the values are read once in order
+        for (&(n, a, b), &s) in layout.layers.iter().zip(sums.iter()) {
+            if checksum(&data[a..b]) != s {
+                return Err(invalid(&format!("corrupted layer {}", n)));
+            }
+        }
+        Ok(Database { layout: layout, start: start + nindex, map: map })
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.layout.len
+    }
+
+    /// Score of the board of code `c`.
+    #[inline]
+    pub fn get(&self, c: usize) -> i8 {
+        self.map.bytes()[self.start + c] as i8
+    }
+
+    /// Rank index of the boards in those of the full game, if the database
+    /// only holds some of them (see `reach`).
+    pub fn index(&self) -> Option<RankSelect> {
+        self.layout.index.as_ref().map(|b| RankSelect::read_from(&mut &b[..]).unwrap())
+    }
+}
+
+impl Scores for Database {
+    #[inline]
+    fn state(&self, i: usize) -> State {
+        State { val: self.get(i), nsuc: None }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{File,OpenOptions,create_dir_all,remove_dir_all};
+    use std::io::{Read,Write,Seek,SeekFrom};
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use kalah::KalahGame;
+    use rules::{Forbidden,NoCapture};
+    use ra::{Driver,MMaped,NaiveRAM,Solver,solve};
+    use reach::{explore,Reachable};
+    use super::{Database,Layout,MAGIC,write};
+
+    #[test]
+    fn database_roundtrip() {
+        let wd = env::temp_dir().join("awari-db-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 8), rules: Forbidden };
+        let table = MMaped::new(&wd, game.len(), log.clone()).unwrap();
+        let mut driver = Driver::new(game, table, log.clone());
+        driver.run();
+        let path = wd.join("table.db");
+        write(&Layout::new(&game), &driver.table, &path).unwrap();
+        let db = Database::open(&path, &game).unwrap();
+        assert_eq!(db.len(), game.len());
+        assert!(db.index().is_none());
+        assert!((0..game.len()).all(|c| db.get(c) == driver.table[c].val));
+
+        // other variants, geometries and games
+        let other = AwariGame { geo: Geometry::new(3, 8), rules: NoCapture };
+        assert!(Database::open(&path, &other).is_err());
+        let other = AwariGame { geo: Geometry::new_full(3, 8), rules: Forbidden };
+        assert!(Database::open(&path, &other).is_err());
+        let other = AwariGame { geo: Geometry::new(3, 9), rules: Forbidden };
+        assert!(Database::open(&path, &other).is_err());
+        assert!(Database::open(&path, &KalahGame { geo: Geometry::new(3, 8) }).is_err());
+
+        // flip a value
+        let mut buf = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut buf).unwrap();
+        let last = buf.len() as u64 - 1;
+        let mut f = OpenOptions::new().write(true).open(&path).unwrap();
+        f.seek(SeekFrom::Start(last)).unwrap();
+        f.write_all(&[buf[last as usize] ^ 1]).unwrap();
+        drop(f);
+        assert!(Database::open(&path, &game).is_err());
+
+        // a huge string length is refused before allocating it
+        let mut f = OpenOptions::new().write(true).open(&path).unwrap();
+        f.write_all(&buf).unwrap();
+        f.seek(SeekFrom::Start(MAGIC.len() as u64 + 8)).unwrap();
+        f.write_all(&[0xff; 8]).unwrap();
+        drop(f);
+        let e = Database::open(&path, &game).err().unwrap();
+        assert_eq!(e.to_string(), "bad string");
+
+        // not a database
+        assert!(Database::open(wd.join("table_mmap"), &game).is_err());
+
+        // the reachable states, with their rank index
+        let set = explore(&game);
+        let reach = Reachable::new(game, &set);
+        let table = solve(reach, NaiveRAM::new(reach.len(), log));
+        write(&Layout::new(&reach), &table, &path).unwrap();
+        let db = Database::open(&path, &reach).unwrap();
+        assert_eq!(db.len(), set.ones());
+        let index = db.index().unwrap();
+        assert_eq!((index.len(), index.ones()), (set.len(), set.ones()));
+        assert!((0..reach.len()).all(|c| db.get(c) == table[c].val));
+        assert!(Database::open(&path, &game).is_err());
+        remove_dir_all(&wd).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp::max;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::convert::AsRef;
+use std::fs::File;
+use std::io::{self,BufReader,BufWriter};
+
+use game::Game;
+use packed::PackedVec;
+use ra::Scores;
+
+
+/// Depth of the states from which no capture is ever forced (cycles).
+pub const NEVER: u8 = 255;
+
+
+/// Number of plies until the game leaves the layer (capture or end of the
+/// game) under optimal play, one byte per board, indexed by the codes of the
+/// boards.
+///
+/// Among the moves reaching the optimal score, a player that is winning (or
+/// drawing) picks the shortest one and a player that is losing the longest
+/// one. This can only be done once the scores of a layer are final, so it is
+/// a separate backward pass on every layer: a breadth-first search from the
+/// states leaving the layer along the optimal 0-valued moves. Depths are
+/// capped to `NEVER - 1`.
+pub struct DepthTable {
+    data: PackedVec,
+}
+
+impl DepthTable {
+    /// Compute the depths of `game` given its finished score `table`.
+    pub fn compute<G: Game, T: Scores>(game: &G, table: &T) -> Self {
+        let mut depth = PackedVec::new(8, game.len());
+        for n in game.layers() {
+            depth_layer(game, table, &mut depth, n);
+        }
+        DepthTable { data: depth }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Depth of the board whose code is `code`.
+    #[inline]
+    pub fn get(&self, code: usize) -> u8 {
+        self.data.get(code)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.data.write_to(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = PackedVec::read_from(&mut BufReader::new(File::open(path)?))?;
+        if data.bits() != 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a depth table"));
+        }
+        Ok(DepthTable { data: data })
+    }
+}
+
+
+fn depth_layer<G, T>(game: &G, table: &T, depth: &mut PackedVec, n: usize)
+        where G: Game, T: Scores {
+    let (lo, hi) = game.layer_range(n);
+    // number of optimal moves inside the layer still to wait for (losing
+    // states only)
+    let mut wait = vec![0u8; hi - lo];
+    let mut queue = VecDeque::new();
+    let mut later = Vec::new();
+
+    for (c, u) in game.iter_layer(n) {
+        let x = table.state(c).val;
+        let (mut inner, mut leave, mut term) = (0, false, true);
+        for (_, k, d, again, _) in game.successors(&u) {
+            term = false;
+            if again {
+                leave = leave || k as i8 + table.state(d).val == x;
+            } else if k > 0 {
+                leave = leave || k as i8 - table.state(d).val == x;
+            } else if -table.state(d).val == x {
+                inner += 1;
+            }
+        }
+        depth.set(c, NEVER);
+        if term {
+            depth.set(c, 0);
+            queue.push_back((c, u));
+        } else if leave && (x >= 0 || inner == 0) {
+            depth.set(c, 1);
+            later.push((c, u));
+        } else if x < 0 {
+            wait[c - lo] = inner;
+            if leave {
+                depth.set(c, 1);
+            }
+        }
+    }
+    // the queue must stay sorted by depth
+    queue.extend(later);
+
+    while let Some((c, u)) = queue.pop_front() {
+        let d = depth.get(c);
+        let e = if d < NEVER - 1 { d + 1 } else { d };
+        let x = table.state(c).val;
+        for (w, p, _) in game.predecessors(&u) {
+            let y = table.state(p).val;
+            if -x != y || (y >= 0 && depth.get(p) != NEVER) {
+                continue;
+            }
+            if y >= 0 {
+                depth.set(p, e);
+                queue.push_back((p, w));
+            } else if wait[p - lo] > 0 {
+                wait[p - lo] -= 1;
+                let f = if depth.get(p) == NEVER { e } else { max(e, depth.get(p)) };
+                depth.set(p, f);
+                if wait[p - lo] == 0 {
+                    queue.push_back((p, w));
+                }
+            }
+        }
+    }
+    // losing states waiting for a move that is never resolved are in a cycle
+    for (c, _) in game.iter_layer(n) {
+        if wait[c - lo] > 0 {
+            depth.set(c, NEVER);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use kalah::KalahGame;
+    use rules::{Forbidden,AllButLast};
+    use ra::{NaiveRAM,solve};
+    use super::{DepthTable,NEVER};
+
+    /// Check the min/max equations defining the depth on every state.
+    fn check<G: Game>(game: G) {
+        let table = &solve(game, NaiveRAM::new(game.len(), Logger::root(Discard, o!())));
+        let depth = DepthTable::compute(&game, table);
+
+        for n in game.layers() {
+            for (c, u) in game.iter_layer(n) {
+                let x = table[c].val;
+                let mut ds = Vec::new();
+                for (_, k, d, again, _) in game.successors(&u) {
+                    if again && k as i8 + table[d].val == x
+                            || !again && k > 0 && k as i8 - table[d].val == x {
+                        ds.push(1);
+                    } else if !again && k == 0 && -table[d].val == x {
+                        let e = depth.get(d);
+                        ds.push(if e == NEVER { NEVER } else { e + 1 });
+                    }
+                }
+                let want = if ds.is_empty() {
+                    0
+                } else if x >= 0 {
+                    *ds.iter().min().unwrap()
+                } else if ds.contains(&NEVER) {
+                    NEVER
+                } else {
+                    *ds.iter().max().unwrap()
+                };
+                assert_eq!(depth.get(c), want, "code {}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn depth_awari() {
+        check(AwariGame { geo: Geometry::new(3, 10), rules: Forbidden });
+        check(AwariGame { geo: Geometry::new(4, 8), rules: AllButLast });
+    }
+
+    #[test]
+    fn depth_kalah() {
+        check(KalahGame { geo: Geometry::new(3, 12) });
+    }
+}
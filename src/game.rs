@@ -0,0 +1,85 @@
+use bitvec::RankSelect;
+
+
+/// Game that can be solved by the retrograde drivers (see
+/// `remarks/comp_analysis.rst`).
+///
+/// States are ranked in `[0, len)` and split in layers (for sowing games, the
+/// number of seeds on the board). Scores are from the point of view of the
+/// player to move and every move either stays in its layer and is 0-valued,
+/// or goes to a layer that was solved before. Moves after which the same
+/// player moves again (extra turns) must go to a layer that was solved before.
+pub trait Game: Copy {
+    type State: Copy;
+    type Iter: Iterator<Item=(usize, Self::State)>;
+    type Successors: Iterator<Item=(Self::State, u8, usize, bool, usize)>;
+    type Predecessors: Iterator<Item=(Self::State, usize, usize)>;
+
+    /// Description of the game and its parameters (used to check that a
+    /// database belongs to the game).
+    fn name(&self) -> String;
+
+    /// Number of states, that is the size of the score table.
+    fn len(&self) -> usize;
+
+    /// Layers to solve, in order.
+    fn layers(&self) -> Vec<usize>;
+
+    /// Range of the codes of the states in layer `n`.
+    fn layer_range(&self, n: usize) -> (usize, usize);
+
+    /// Iterate on the states of layer `n` whose code is in `[start,end)`,
+    /// together with their code, in increasing order.
+    fn iter_range(&self, n: usize, start: usize, end: usize) -> Self::Iter;
+
+    /// Iterate on every state of layer `n`.
+    fn iter_layer(&self, n: usize) -> Self::Iter {
+        let (a, b) = self.layer_range(n);
+        self.iter_range(n, a, b)
+    }
+
+    /// Upper bound on the absolute value of the scores in layer `n`.
+    fn max_score(&self, n: usize) -> i8;
+
+    /// Test if the scores of layer `n` have the parity of `max_score(n)` as
+    /// soon as the scores of the layers solved before have theirs (states in
+    /// a cycle, worth 0, excepted). The drivers then only run every other
+    /// saturation level while no cycle broke the parity.
+    fn same_parity(&self, _n: usize) -> bool {
+        false
+    }
+
+    fn encode(&self, u: &Self::State) -> usize;
+
+    /// Test if `u` has a code. The states that can't be reached by a move
+    /// may have none.
+    fn has_code(&self, _u: &Self::State) -> bool {
+        true
+    }
+
+    /// Starting position of the game.
+    fn start(&self) -> Self::State;
+
+    /// Iterate on the successors of `u`, together with the reward of the move,
+    /// their code, whether the same player moves again and the move (pit).
+    fn successors(&self, u: &Self::State) -> Self::Successors;
+
+    /// Play the move (pit) `i` of `u`, returning the same as `successors`
+    /// (without the move), or `None` if it is not a legal move (`i` may be any
+    /// index below `MAX_PITS`).
+    fn play(&self, u: &Self::State, i: usize) -> Option<(Self::State, u8, usize, bool)>;
+
+    /// Iterate on the predecessors of `u` through 0-valued moves, together
+    /// with their code and the move (pit) leading to `u`.
+    fn predecessors(&self, u: &Self::State) -> Self::Predecessors;
+
+    /// Final score of a state without successors.
+    fn terminal_score(&self, u: &Self::State) -> i8;
+
+    /// Rank index of the codes of the states in those of the game they are
+    /// taken from, if they are a subset of it (see `reach`). It is stored in
+    /// databases.
+    fn index(&self) -> Option<&RankSelect> {
+        None
+    }
+}
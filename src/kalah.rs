@@ -0,0 +1,472 @@
+use std::ops::{Deref,DerefMut};
+use std::iter::Iterator;
+use std::fmt;
+
+use MAX_FPITS;
+use utils::{Geometry,binom,binom_maxinv};
+use game::Game;
+
+
+/// Representation of a kalah board configuration. The stores are not
+/// represented: only the seeds left on the board matter for the rest of the
+/// game.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct Kalah {
+    geo: Geometry,
+    pits: [u8; MAX_FPITS],
+}
+
+
+impl Deref for Kalah {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.pits[..self.geo.fpits()]
+    }
+}
+
+
+impl DerefMut for Kalah {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let fpits = self.geo.fpits();
+        &mut self.pits[..fpits]
+    }
+}
+
+
+impl fmt::Debug for Kalah {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
+        write!(f, "\n|")?;
+        for i in (pits..fpits).rev() {
+            write!(f, "{:2}|", self[i])?;
+        }
+        write!(f, "\n|")?;
+        for i in 0..pits {
+            write!(f, "{:2}|", self[i])?;
+        }
+        return Ok(());
+    }
+}
+
+
+/// Number of boards with less than `n` seeds.
+#[inline]
+fn enc_min(geo: Geometry, n: usize) -> usize {
+    binom(geo.fpits(), n + geo.fpits() - 1)
+}
+
+
+impl Kalah {
+    /// Instanciate the canonical starting board configuration.
+    pub fn new(geo: Geometry) -> Self {
+        let mut u = Kalah::empty(geo);
+        for i in 0..geo.fpits() {
+            u[i] = geo.start_seeds() as u8;
+        }
+        return u;
+    }
+
+    /// Instanciate a board without any seed.
+    pub fn empty(geo: Geometry) -> Self {
+        Kalah { geo: geo, pits: [0; MAX_FPITS] }
+    }
+
+    /// Iterate on the board configurations with `n` seeds whose code is in
+    /// range `[start,end)`.
+    pub fn iter_config_range(geo: Geometry, n: usize, start: usize, end: usize) -> Iter {
+        assert!(enc_min(geo, n) <= start && start <= end && end <= enc_min(geo, n + 1),
+                "code range out of layer");
+        let x = 1 << geo.fpits() - 1;
+        let mut it = Iter { geo: geo,
+                            curr: x - 1,
+                            last: (x - 1) << n,
+                            mask: x << n,
+                            code: start.wrapping_sub(1),
+                            end: end };
+        if start < end {
+            // the bit-vector has a one after the seeds of each pit
+            let u = Kalah::decode(geo, start);
+            let mut c = 0;
+            it.curr = 0;
+            for i in 0..geo.fpits()-1 {
+                c += u[i] as usize;
+                it.curr |= 1 << (c + i);
+            }
+        }
+        return it;
+    }
+
+    /// Return a compact encoding of a kalah board as an integer, every
+    /// configuration of the board is used.
+    #[inline]
+    pub fn encode(&self) -> usize {
+        let (mut g, mut c) = (0, 0);
+        for i in 0..self.geo.fpits() {
+            c += self[i] as usize;
+            g += binom(i + 1, c + i);
+        }
+        return g;
+    }
+
+    /// Compute the board configuration whose code is `code` (inverse of
+    /// `encode`).
+    pub fn decode(geo: Geometry, code: usize) -> Self {
+        let fpits = geo.fpits();
+        let (mut x, b) = binom_maxinv(geo, fpits, code);
+        let mut g = code - b;
+        let mut u = Kalah::empty(geo);
+        for i in (0..fpits-1).rev() {
+            let (y, b) = binom_maxinv(geo, i + 1, g);
+            g -= b;
+            u[i+1] = (x - y - 1) as u8;
+            x = y;
+        }
+        u[0] = x as u8;
+        return u;
+    }
+
+    /// Test if the game is over, that is if one of the players has no seed.
+    #[inline]
+    pub fn finished(&self) -> bool {
+        let pits = self.geo.pits;
+        self[..pits].iter().all(|&x| x == 0) || self[pits..].iter().all(|&x| x == 0)
+    }
+
+    /// Iterate on every legal successor configuration of the current board
+    /// together with the reward of the move, its code, whether the same
+    /// player moves again and the pit played.
+    #[inline]
+    pub fn successors(&self) -> Successors {
+        Successors { board: *self, i: 0 }
+    }
+
+    /// Iterate on every legal predecessor through a move capturing nothing,
+    /// together with its code and the pit it plays.
+    #[inline]
+    pub fn predecessors(&self) -> Predecessors {
+        let mut cpy = *self;
+        cpy.rotate();
+        Predecessors { cpy: cpy, i: 0, j: 1 }
+    }
+
+    /// Rotate the board (simulating a player change).
+    #[inline]
+    fn rotate(&mut self) {
+        let pits = self.geo.pits;
+        for i in 0..pits {
+            self.swap(i, i + pits);
+        }
+    }
+
+    /// Play the pit `i` and return the number of seeds put in the store and
+    /// whether the same player moves again. Panics if this is not a valid
+    /// move (`self[i] == 0`).
+    fn play(&mut self, i: usize) -> (u8, bool) {
+        let (pits, fpits) = (self.geo.pits, self.geo.fpits());
+        debug_assert!(i < pits && self[i] > 0);
+
+        // positions `0..pits` are the own pits, `pits` is the store and the
+        // following ones are the opponent pits
+        let (mut n, mut k, mut p) = (self[i], 0, i);
+        self[i] = 0;
+        while n > 0 {
+            p = (p + 1) % (fpits + 1);
+            if p == pits {
+                k += 1;
+            } else if p < pits {
+                self[p] += 1;
+            } else {
+                self[p-1] += 1;
+            }
+            n -= 1;
+        }
+
+        if p == pits {
+            return (k, true);
+        }
+        // ending in an empty own pit captures the opposite pit
+        if p < pits && self[p] == 1 && self[fpits-1-p] > 0 {
+            k += 1 + self[fpits-1-p];
+            self[p] = 0;
+            self[fpits-1-p] = 0;
+        }
+        self.rotate();
+        return (k, false);
+    }
+}
+
+
+/// Iterator on the successors of a board, see `Kalah::successors`.
+pub struct Successors {
+    board: Kalah,
+    i: usize,
+}
+
+
+impl Iterator for Successors {
+    type Item = (Kalah, u8, usize, bool, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(Kalah, u8, usize, bool, usize)> {
+        if self.board.finished() {
+            return None;
+        }
+        while self.i < self.board.geo.pits {
+            let i = self.i;
+            self.i += 1;
+            if self.board[i] > 0 {
+                let mut s = self.board;
+                let (k, again) = s.play(i);
+                return Some((s, k, s.encode(), again, i));
+            }
+        }
+        return None;
+    }
+}
+
+
+/// Iterator on the predecessors of a board through moves capturing nothing,
+/// see `Kalah::predecessors`. Such a move starts from pit `i` and ends in pit
+/// `j` on the same side without reaching the store.
+pub struct Predecessors {
+    cpy: Kalah,
+    i: usize,
+    j: usize,
+}
+
+
+impl Iterator for Predecessors {
+    type Item = (Kalah, usize, usize);
+
+    fn next(&mut self) -> Option<(Kalah, usize, usize)> {
+        let (pits, fpits) = (self.cpy.geo.pits, self.cpy.geo.fpits());
+        // the predecessor was not finished
+        if self.cpy[pits..].iter().all(|&x| x == 0) {
+            return None;
+        }
+        while self.i < pits {
+            let (i, j) = (self.i, self.j);
+            if self.cpy[i] != 0 || j >= pits || self.cpy[j] == 0 {
+                self.i += 1;
+                self.j = self.i + 1;
+                continue;
+            }
+            self.j += 1;
+            // the last seed was sown in an empty pit facing a non-empty one
+            if self.cpy[j] == 1 && self.cpy[fpits-1-j] > 0 {
+                continue;
+            }
+            let mut s = self.cpy;
+            for p in i+1..j+1 {
+                s[p] -= 1;
+            }
+            s[i] = (j - i) as u8;
+            return Some((s, s.encode(), i));
+        }
+        return None;
+    }
+}
+
+
+/// Iterator for kalah board configurations with a given number of seeds.
+pub struct Iter {
+    geo: Geometry,
+    curr: usize,
+    last: usize,
+    mask: usize,
+    code: usize,
+    end: usize,
+}
+
+
+impl Iterator for Iter {
+    type Item = (usize, Kalah);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, Kalah)> {
+        if self.curr > self.last || self.code.wrapping_add(1) >= self.end {
+            return None;
+        }
+        // extract the board
+        let mut x = self.curr | self.mask;
+        let mut s = Kalah::empty(self.geo);
+        for i in 0..self.geo.fpits() {
+            let tz = x.trailing_zeros();
+            s[i] = tz as u8;
+            x >>= tz + 1;
+        }
+
+        // increment state
+        let c = self.curr;
+        let t = c | (c - 1);
+        self.curr = (t+1) | (((!t & (t+1)) - 1) >> (c.trailing_zeros() + 1));
+
+        self.code = self.code.wrapping_add(1);
+        debug_assert!(self.code == s.encode());
+        return Some((self.code, s));
+    }
+}
+
+
+/// Kalah with a given geometry, as solved by the retrograde drivers. The
+/// layers are the numbers of seeds on the board and the scores count the seeds
+/// that will still be put in the stores. When a player has no seed left, each
+/// player takes the seeds on his side.
+#[derive(Copy, Clone)]
+pub struct KalahGame {
+    pub geo: Geometry,
+}
+
+
+impl Game for KalahGame {
+    type State = Kalah;
+    type Iter = Iter;
+    type Successors = Successors;
+    type Predecessors = Predecessors;
+
+    fn name(&self) -> String {
+        format!("kalah {} {}", self.geo.pits, self.geo.seeds)
+    }
+
+    fn len(&self) -> usize {
+        enc_min(self.geo, self.geo.seeds + 1)
+    }
+
+    fn layers(&self) -> Vec<usize> {
+        (0..self.geo.seeds+1).collect()
+    }
+
+    fn layer_range(&self, n: usize) -> (usize, usize) {
+        (enc_min(self.geo, n), enc_min(self.geo, n + 1))
+    }
+
+    fn iter_range(&self, n: usize, start: usize, end: usize) -> Iter {
+        Kalah::iter_config_range(self.geo, n, start, end)
+    }
+
+    fn max_score(&self, n: usize) -> i8 {
+        n as i8
+    }
+
+    /// Every seed ends in a store.
+    fn same_parity(&self, _: usize) -> bool {
+        true
+    }
+
+    #[inline]
+    fn encode(&self, u: &Kalah) -> usize {
+        u.encode()
+    }
+
+    fn start(&self) -> Kalah {
+        Kalah::new(self.geo)
+    }
+
+    #[inline]
+    fn successors(&self, u: &Kalah) -> Successors {
+        u.successors()
+    }
+
+    fn play(&self, u: &Kalah, i: usize) -> Option<(Kalah, u8, usize, bool)> {
+        if i >= self.geo.pits || u[i] == 0 || u.finished() {
+            return None;
+        }
+        let mut v = *u;
+        let (k, again) = v.play(i);
+        Some((v, k, v.encode(), again))
+    }
+
+    #[inline]
+    fn predecessors(&self, u: &Kalah) -> Predecessors {
+        u.predecessors()
+    }
+
+    fn terminal_score(&self, u: &Kalah) -> i8 {
+        let pits = self.geo.pits;
+        let own = u[..pits].iter().fold(0, |a, &x| a + x as i8);
+        let opp = u[pits..].iter().fold(0, |a, &x| a + x as i8);
+        own - opp
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::max;
+    use rand::Rng;
+    use quickcheck::{Arbitrary,Gen};
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use ra::{NaiveRAM,solve};
+    use super::{Kalah,KalahGame};
+
+    const GEO: Geometry = Geometry { pits: 6, seeds: 36, full: false };
+
+    impl Arbitrary for Kalah {
+        fn arbitrary<G: Gen>(g: &mut G) -> Kalah {
+            let mut b = Kalah::empty(GEO);
+            let n = g.gen_range(0, GEO.seeds + 1);
+            for _ in 0..n {
+                let i = g.gen_range(0, GEO.fpits());
+                b[i] += 1;
+            }
+            return b;
+        }
+    }
+
+    #[quickcheck]
+    fn coding_bijective(u: Kalah) -> bool {
+        u == Kalah::decode(GEO, u.encode())
+    }
+
+    #[quickcheck]
+    fn succ_in_pred(u: Kalah) -> bool {
+        u.successors()
+         .all(|(v, k, c, again, i)| c == v.encode()
+                                    && (k > 0 || again || v.predecessors()
+                                                           .any(|(w, d, j)| u == w && i == j
+                                                                            && d == w.encode())))
+    }
+
+    #[quickcheck]
+    fn pred_in_succ(u: Kalah) -> bool {
+        u.predecessors()
+         .all(|(v, _, i)| v.successors()
+                           .any(|(w, k, _, again, j)| k == 0 && !again && u == w && i == j))
+    }
+
+    #[test]
+    fn iter_config_range_slice() {
+        let game = KalahGame { geo: Geometry::new(3, 12) };
+        for n in game.layers() {
+            let (a, b) = game.layer_range(n);
+            let m = (a + b) / 2;
+            assert!(game.iter_range(n, m, b).eq(game.iter_layer(n).skip(m - a)));
+        }
+    }
+
+    /// Check that the scores of the table satisfy the minimax equations.
+    #[test]
+    fn solve_small() {
+        let game = KalahGame { geo: Geometry::new(3, 12) };
+        let table = solve(game, NaiveRAM::new(game.len(), Logger::root(Discard, o!())));
+
+        for n in game.layers() {
+            for (c, u) in game.iter_layer(n) {
+                let mut best = None;
+                for (_, k, d, again, _) in game.successors(&u) {
+                    let v = table[d].val;
+                    let x = if again { k as i8 + v } else { k as i8 - v };
+                    best = Some(best.map_or(x, |b| max(b, x)));
+                }
+                let score = best.unwrap_or(game.terminal_score(&u));
+                assert_eq!(table[c].val, score);
+            }
+        }
+    }
+}
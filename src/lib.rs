@@ -1,4 +1,6 @@
-#![feature(placement_in_syntax,box_heap,nonzero)]
+#![feature(nonzero)]
+#![feature(integer_atomics)]
+#![feature(repr_align, attr_literals)]
 
 #![cfg_attr(test, feature(test, plugin))]
 #![cfg_attr(test, plugin(quickcheck_macros))]
@@ -7,15 +9,37 @@
 #[cfg(test)] extern crate test;
 #[cfg(test)] extern crate rand;
 #[macro_use] extern crate slog;
+#[macro_use] extern crate lazy_static;
 extern crate libc;
 extern crate core;
 extern crate tempfile;
+extern crate crossbeam;
 
 
 pub mod utils;
+pub mod game;
 pub mod awari;
+pub mod kalah;
+pub mod rules;
 pub mod ra;
+pub mod par;
+pub mod checkpoint;
+pub mod packed;
+pub mod wdl;
+pub mod depth;
+pub mod verify;
+pub mod best;
+pub mod reach;
+pub mod bitvec;
+pub mod db;
 mod storage;
 
-// include constants (see `build.rs`)
-include!(concat!(env!("OUT_DIR"), "/params.rs"));
+pub use utils::Geometry;
+
+/// Largest supported number of pits per player.
+pub const MAX_PITS: usize = 8;
+/// Largest supported number of pits on the board.
+pub const MAX_FPITS: usize = 2 * MAX_PITS;
+/// Largest supported number of seeds (board configurations are enumerated
+/// using bit-vectors of `FPITS + SEEDS` bits).
+pub const MAX_SEEDS: usize = 48;
@@ -2,13 +2,258 @@
 extern crate slog_json;
 extern crate awari;
 
-use slog::Drain;
+use slog::{Drain,Logger};
 use std::sync::Mutex;
 use std::io::BufWriter;
-use std::fs::OpenOptions;
+use std::fs::{OpenOptions,create_dir_all};
+use std::path::Path;
+use std::env;
+use std::fmt::Debug;
 
-use awari::{PITS,SEEDS,NBOARDS};
-use awari::ra::{Driver,MMaped};
+use awari::Geometry;
+use awari::game::Game;
+use awari::awari::AwariGame;
+use awari::kalah::KalahGame;
+use awari::ra::{Table,Scores,Driver,MMaped,Paged,Layered,Solver};
+use awari::par::ParTable;
+use awari::par::ParDriver;
+use awari::checkpoint::Checkpoint;
+use awari::verify::verify;
+use awari::depth::DepthTable;
+use awari::best::BestMoves;
+use awari::db::{self,Database,Layout};
+use awari::reach::{explore,Reachable};
+use awari::bitvec::RankSelect;
+use awari::rules::{Rules,Variant,Ending,Forbidden,NoCapture,CaptureAll,AllButLast};
+
+
+/// Pages of `Paged` tables, in states, and number of pages kept in memory.
+const PAGE: usize = 1 << 16;
+const PAGES: usize = 4096;
+
+
+/// Backend storing the table.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Storage {
+    MMaped,
+    Paged,
+    Layered,
+}
+
+
+/// What a run does with the table in `tmp/<dir>`, see `solve`.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    Solve,
+    Resume,
+    Extend,
+    Verify,
+    Depth,
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match *self {
+            Mode::Solve => "solve",
+            Mode::Resume => "resume",
+            Mode::Extend => "extend",
+            Mode::Verify => "verify",
+            Mode::Depth => "depth",
+        }
+    }
+}
+
+
+/// Options of a run, given on the command line.
+struct Options {
+    threads: usize,
+    mode: Mode,
+    /// record an optimal move of every board
+    best: bool,
+    /// keep the boards that may stabilize at each level (sequential driver)
+    buckets: bool,
+    /// only solve the states reachable from the start
+    reachable: bool,
+    storage: Storage,
+}
+
+
+/// Check the finished `table` of `game` (`verify` mode) or compute the depths
+/// of its boards in `<wd>/depth` (`depth` mode).
+fn inspect<G, T>(game: &G, table: &T, wd: &Path, mode: Mode, root: &Logger)
+        where G: Game, G::State: Debug, T: Scores {
+    if mode == Mode::Verify {
+        println!("{} mismatches", verify(game, table, root).len());
+    } else {
+        let path = wd.join("depth");
+        DepthTable::compute(game, table).save(&path).unwrap();
+        info!(root, "depth table written"; "path" => path.display().to_string());
+    }
+}
+
+
+/// Write the database of the finished `table` of `game` in `<wd>/table.db`.
+///
+/// This is done once the driver returns, right after `Table::finish_hook`:
+/// the tables don't know the layout of the game, and the hook has to flush
+/// (or map) every layer before the scores can be read in order.
+fn save_database<G: Game, T: Scores>(game: &G, table: &T, wd: &Path, root: &Logger) {
+    let path = wd.join("table.db");
+    db::write(&Layout::new(game), table, &path).unwrap();
+    info!(root, "database written"; "path" => path.display().to_string());
+}
+
+
+/// Solve `game` (or extend the table of `old`) in `table` with the sequential
+/// driver, see `solve`.
+fn run_seq<G: Game, T: Table>(game: G, old: Option<G>, table: T, wd: &Path, opts: &Options,
+                              root: Logger) {
+    let best = if opts.best { Some(BestMoves::new(game.len())) } else { None };
+    let mut driver = Driver { checkpoint: Some(Checkpoint::new(wd, &game)),
+                              best: best,
+                              buckets: opts.buckets,
+                              ..Driver::new(game, table, root) };
+    match opts.mode {
+        Mode::Solve => driver.run(),
+        Mode::Resume => driver.resume().unwrap(),
+        Mode::Extend => driver.extend(&old.expect("missing the old number of seeds")),
+        m => panic!("{} does not solve", m.name()),
+    }
+    if let Some(ref best) = driver.best {
+        let path = wd.join("best");
+        best.save(&path).unwrap();
+        info!(driver.logger, "best moves written"; "path" => path.display().to_string());
+    }
+    save_database(&driver.game, &driver.table, wd, &driver.logger);
+}
+
+
+/// Solve `game` in `table` with `opts.threads` workers, see `solve`.
+fn run_par<G: Game + Sync, T: ParTable>(game: G, table: T, wd: &Path, opts: &Options,
+                                        root: Logger) {
+    let mut driver = ParDriver { game: game,
+                                 table: table,
+                                 logger: root,
+                                 threads: opts.threads,
+                                 checkpoint: Some(Checkpoint::new(wd, &game)) };
+    match opts.mode {
+        Mode::Solve => driver.run(),
+        Mode::Resume => driver.resume().unwrap(),
+        m => panic!("{} is not supported by the parallel driver", m.name()),
+    }
+    save_database(&driver.game, &driver.table, wd, &driver.logger);
+}
+
+
+/// Solve the given game with `threads` workers, storing the table in
+/// `tmp/<dir>`, and its database in `tmp/<dir>/table.db` (with the rank index
+/// of reachable games). With `best`, also record an optimal move of every
+/// board in `tmp/<dir>/best`. With `resume`, continue the run recorded there,
+/// with `verify` or `depth` inspect the finished table (or database) found
+/// there instead (see `inspect`) and with `extend`, extend the table of `old`
+/// found there (sequentially).
+fn solve<G: Game + Sync>(game: G, old: Option<G>, dir: &str, opts: &Options, root: Logger)
+        where G::State: Debug {
+    let wd = Path::new("tmp").join(dir);
+    create_dir_all(&wd).unwrap();
+    let mode = opts.mode;
+
+    info!(root, "params"; "game" => game.name(), "nboards" => game.len(),
+                          "threads" => opts.threads, "mode" => mode.name(),
+                          "best" => opts.best);
+
+    if mode == Mode::Verify || mode == Mode::Depth {
+        let db = wd.join("table.db");
+        let log = root.new(o!());
+        if db.exists() {
+            inspect(&game, &Database::open(&db, &game).unwrap(), &wd, mode, &root);
+        } else {
+            match opts.storage {
+                Storage::MMaped => {
+                    let table = MMaped::open(&wd, game.len(), log).unwrap();
+                    inspect(&game, &table, &wd, mode, &root);
+                },
+                Storage::Paged => {
+                    let table = Paged::open(&wd, game.len(), PAGE, PAGES, log).unwrap();
+                    inspect(&game, &table, &wd, mode, &root);
+                },
+                Storage::Layered => {
+                    inspect(&game, &Layered::open(&wd, &game, log).unwrap(), &wd, mode, &root);
+                },
+            }
+        }
+        return;
+    }
+    match opts.storage {
+        Storage::MMaped => {
+            let table = MMaped::new(&wd, game.len(), root.new(o!())).unwrap();
+            if opts.threads > 1 && mode != Mode::Extend {
+                run_par(game, table, &wd, opts, root);
+            } else {
+                run_seq(game, old, table, &wd, opts, root);
+            }
+        },
+        Storage::Paged => {
+            let table = Paged::new(&wd, game.len(), PAGE, PAGES, root.new(o!())).unwrap();
+            run_seq(game, old, table, &wd, opts, root);
+        },
+        Storage::Layered => {
+            // the layers solved before are read by the driver when resuming
+            let table = if mode == Mode::Resume {
+                Layered::open(&wd, &game, root.new(o!())).unwrap()
+            } else {
+                Layered::new(&wd, &game, root.new(o!()))
+            };
+            if opts.threads > 1 {
+                run_par(game, table, &wd, opts, root);
+            } else {
+                run_seq(game, old, table, &wd, opts, root);
+            }
+        },
+    }
+}
+
+
+/// Same as `solve`, but with `reachable` only solve the states reachable from
+/// the start, in `tmp/<dir>-reachable`. Their rank index is computed when
+/// solving and saved in `tmp/<dir>-reachable/rank`, the other modes read it.
+fn solve_game<G: Game + Sync>(game: G, old: Option<G>, dir: &str, opts: &Options, root: Logger)
+        where G::State: Debug {
+    if opts.reachable {
+        let dir = format!("{}-reachable", dir);
+        let path = Path::new("tmp").join(&dir).join("rank");
+        let set = if opts.mode == Mode::Solve {
+            let set = explore(&game);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            set.save(&path).unwrap();
+            set
+        } else {
+            RankSelect::open(&path).unwrap()
+        };
+        assert_eq!(set.len(), game.len(), "rank index of another game");
+        info!(root, "reachable"; "states" => set.ones());
+        solve(Reachable::new(game, &set), None, &dir, opts, root);
+    } else {
+        solve(game, old, dir, opts, root);
+    }
+}
+
+
+/// Solve awari with the given rule-set, in `tmp/<rules>-<ending>` (with a
+/// `-full` suffix for the full geometries).
+fn solve_awari<R: Rules + Sync>(geo: Geometry, rules: R, old: Option<Geometry>, opts: &Options,
+                                root: Logger) {
+    let dir = format!("{}-{}{}", rules.name(), rules.ending().name(),
+                      if geo.full { "-full" } else { "" });
+    solve_game(AwariGame { geo: geo, rules: rules },
+               old.map(|g| AwariGame { geo: g, rules: rules }),
+               &dir, opts, root);
+}
+
+
+/// Flags accepted anywhere on the command line.
+const FLAGS: &'static [&'static str] = &["--best", "--buckets", "--full", "--reachable",
+                                         "--paged", "--layered"];
 
 
 fn main() {
@@ -24,9 +269,87 @@ fn main() {
         o!()
     );
 
-    info!(root, "params"; "pits" => PITS, "seeds" => SEEDS, "nboards" => NBOARDS);
+    // flags can be anywhere, the other arguments are positional
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|a| {
+        a.starts_with("--")
+    });
+    if let Some(f) = flags.iter().find(|f| !FLAGS.contains(&&f[..])) {
+        panic!("unknown flag: {}", f);
+    }
+    let flag = |name| flags.iter().any(|f| f == name);
+    // also encode the boards with SEEDS-1 seeds
+    let full = flag("--full");
+
+    let pits = args.get(1).map_or(6, |s| s.parse().unwrap());
+    let geometry = |seeds| {
+        if full { Geometry::new_full(pits, seeds) } else { Geometry::new(pits, seeds) }
+    };
+    let geo = geometry(args.get(2).map_or(4, |s| s.parse().unwrap()));
+
+    let ending = match args.get(4).map_or("opponent", |s| &s[..]) {
+        "opponent" => Ending::Opponent,
+        "owner" => Ending::Owner,
+        "split" => Ending::Split,
+        e => panic!("unknown ending: {}", e),
+    };
+
+    let opts = Options {
+        threads: args.get(5).map_or(1, |s| s.parse().unwrap()),
+        mode: match args.get(6).map_or("solve", |s| &s[..]) {
+            "solve" => Mode::Solve,
+            "resume" => Mode::Resume,
+            "extend" => Mode::Extend,
+            "verify" => Mode::Verify,
+            "depth" => Mode::Depth,
+            m => panic!("unknown mode: {}", m),
+        },
+        best: flag("--best"),
+        buckets: flag("--buckets"),
+        reachable: flag("--reachable"),
+        storage: match (flag("--paged"), flag("--layered")) {
+            (false, false) => Storage::MMaped,
+            (true, false) => Storage::Paged,
+            (false, true) => Storage::Layered,
+            _ => panic!("only one storage can be selected"),
+        },
+    };
+    let old = args.get(7).map(|s| geometry(s.parse().unwrap()));
+    if opts.best && opts.threads > 1 {
+        panic!("best moves are only recorded by the sequential driver");
+    }
+    if opts.buckets && opts.threads > 1 && opts.mode != Mode::Extend {
+        panic!("buckets are only kept by the sequential driver");
+    }
+    if opts.best && (opts.mode == Mode::Resume || opts.mode == Mode::Extend) {
+        panic!("best moves are only recorded by a full run");
+    }
+    if opts.storage == Storage::Paged && opts.threads > 1 {
+        panic!("paged tables can't be shared between threads");
+    }
+    if opts.reachable && opts.mode == Mode::Extend {
+        panic!("reachable tables can't be extended");
+    }
+    if opts.storage == Storage::Layered && opts.mode == Mode::Extend {
+        panic!("the layers of a layered table can't be moved");
+    }
 
-    let mut driver = Driver { table: MMaped::new("tmp", root.new(o!())).unwrap(),
-                              logger: root };
-    driver.run();
+    match args.get(3).map_or("forbidden", |s| &s[..]) {
+        "kalah" => {
+            // every kalah board is encoded
+            if full {
+                panic!("--full only applies to awari");
+            }
+            solve_game(KalahGame { geo: geo }, old.map(|g| KalahGame { geo: g }),
+                       "kalah", &opts, root)
+        },
+        "forbidden" => solve_awari(geo, Variant { slam: Forbidden, ending: ending },
+                                   old, &opts, root),
+        "nocapture" => solve_awari(geo, Variant { slam: NoCapture, ending: ending },
+                                   old, &opts, root),
+        "captureall" => solve_awari(geo, Variant { slam: CaptureAll, ending: ending },
+                                    old, &opts, root),
+        "allbutlast" => solve_awari(geo, Variant { slam: AllButLast, ending: ending },
+                                    old, &opts, root),
+        r => panic!("unknown rule-set: {}", r),
+    }
 }
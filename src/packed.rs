@@ -0,0 +1,171 @@
+use std::io::{self,Read,Write};
+
+
+/// Vector of `bits`-bit unsigned integers (`bits <= 8`) packed in 64-bit
+/// words, an entry may straddle two words.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PackedVec {
+    bits: usize,
+    len: usize,
+    words: Vec<u64>,
+}
+
+
+pub fn write_u64<W: Write>(w: &mut W, x: u64) -> io::Result<()> {
+    let mut buf = [0; 8];
+    for i in 0..8 {
+        buf[i] = (x >> (8 * i)) as u8;
+    }
+    w.write_all(&buf)
+}
+
+
+pub fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(buf.iter().rev().fold(0, |x, &b| (x << 8) | b as u64))
+}
+
+
+impl PackedVec {
+    /// Instanciate a vector of `len` zeros.
+    pub fn new(bits: usize, len: usize) -> Self {
+        assert!(0 < bits && bits <= 8, "unsupported entry size");
+        PackedVec {
+            bits: bits,
+            len: len,
+            words: vec![0; (bits * len + 63) / 64],
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize) -> u8 {
+        debug_assert!(i < self.len);
+        let (w, o) = divmod64(i * self.bits);
+        let mut x = self.words[w] >> o;
+        if o + self.bits > 64 {
+            x |= self.words[w+1] << (64 - o);
+        }
+        (x & self.mask()) as u8
+    }
+
+    #[inline]
+    pub fn set(&mut self, i: usize, x: u8) {
+        debug_assert!(i < self.len && (x as u64) <= self.mask());
+        let (w, o) = divmod64(i * self.bits);
+        let (m, x) = (self.mask(), x as u64);
+        self.words[w] = (self.words[w] & !(m << o)) | (x << o);
+        if o + self.bits > 64 {
+            let s = 64 - o;
+            self.words[w+1] = (self.words[w+1] & !(m >> s)) | (x >> s);
+        }
+    }
+
+    #[inline]
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    /// Serialize the vector (little-endian entry size, length and words).
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_u64(w, self.bits as u64)?;
+        write_u64(w, self.len as u64)?;
+        for &x in &self.words {
+            write_u64(w, x)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a vector written by `write_to`.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let bits = read_u64(r)? as usize;
+        let len = read_u64(r)? as usize;
+        if bits == 0 || bits > 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "unsupported entry size"));
+        }
+        let size = match bits.checked_mul(len) {
+            Some(n) => ((n / 64 + (n % 64 != 0) as usize) * 8) as u64,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              "bad length")),
+        };
+
+        // only allocate what the input actually holds
+        let mut buf = Vec::new();
+        r.take(size).read_to_end(&mut buf)?;
+        if (buf.len() as u64) < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "truncated vector"));
+        }
+        let words = buf.chunks(8)
+                       .map(|b| b.iter().rev().fold(0, |x, &y| (x << 8) | y as u64))
+                       .collect();
+        Ok(PackedVec { bits: bits, len: len, words: words })
+    }
+}
+
+
+#[inline]
+fn divmod64(n: usize) -> (usize, usize) {
+    (n / 64, n % 64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::PackedVec;
+
+    #[quickcheck]
+    fn set_get(bits: usize, xs: Vec<u8>) -> bool {
+        let bits = bits % 8 + 1;
+        let xs: Vec<u8> = xs.into_iter().map(|x| x & ((1 << bits) - 1) as u8).collect();
+        let mut v = PackedVec::new(bits, xs.len());
+        for (i, &x) in xs.iter().enumerate() {
+            v.set(i, x);
+        }
+        // overwrite once more to check that neighbours are preserved
+        for (i, &x) in xs.iter().enumerate().rev() {
+            v.set(i, x);
+        }
+        xs.iter().enumerate().all(|(i, &x)| v.get(i) == x)
+    }
+
+    #[quickcheck]
+    fn serialize(bits: usize, xs: Vec<u8>) -> bool {
+        let bits = bits % 8 + 1;
+        let mut v = PackedVec::new(bits, xs.len());
+        for (i, &x) in xs.iter().enumerate() {
+            v.set(i, x & ((1 << bits) - 1) as u8);
+        }
+        let mut buf = Vec::new();
+        v.write_to(&mut buf).unwrap();
+        PackedVec::read_from(&mut &buf[..]).unwrap() == v
+    }
+
+    #[test]
+    fn read_bad_length() {
+        let mut v = PackedVec::new(3, 100);
+        v.set(99, 5);
+        let mut buf = Vec::new();
+        v.write_to(&mut buf).unwrap();
+        assert!(PackedVec::read_from(&mut &buf[..buf.len()-1]).is_err());
+        // a huge length is rejected without allocating it
+        for &len in &[1 << 40, usize::max_value()] {
+            let mut b = buf.clone();
+            for i in 0..8 {
+                b[8+i] = ((len as u64) >> (8 * i)) as u8;
+            }
+            assert!(PackedVec::read_from(&mut &b[..]).is_err());
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::cmp::min;
+use std::sync::atomic::{AtomicU16,AtomicUsize,Ordering};
+
+use crossbeam;
+use slog::Logger;
+
+use game::Game;
+use ra::{State,Table,Solver,initial_score};
+use checkpoint::Checkpoint;
+
+
+/// Table whose states can be accessed concurrently.
+pub trait ParTable: Table + Sync {
+    /// Return the state at index `i` as an atomic cell (see `State::pack`).
+    fn atomic(&self, i: usize) -> &AtomicU16;
+}
+
+
+/// Apply `f` to the state stored in `cell` with a compare-and-swap loop and
+/// return its result.
+#[inline]
+fn modify<F: Fn(&mut State) -> Option<i8>>(cell: &AtomicU16, f: F) -> Option<i8> {
+    let mut old = cell.load(Ordering::Acquire);
+    loop {
+        let mut s = State::unpack(old);
+        let r = f(&mut s);
+        match cell.compare_exchange_weak(old, s.pack(), Ordering::AcqRel,
+                                         Ordering::Acquire) {
+            Ok(_) => return r,
+            Err(x) => old = x,
+        }
+    }
+}
+
+
+/// Multi-threaded version of `ra::Driver`, every layer is split in `threads`
+/// ranges of codes handled by separate workers. It computes the same table,
+/// but doesn't record best moves nor keep buckets.
+pub struct ParDriver<G: Game + Sync, T: ParTable> {
+    pub game: G,
+    pub table: T,
+    pub logger: Logger,
+    pub threads: usize,
+    pub checkpoint: Option<Checkpoint>,
+}
+
+
+impl<G: Game + Sync, T: ParTable> ParDriver<G, T> {
+    /// Split the codes of the layer `n` in ranges, one per worker.
+    fn ranges(&self, n: usize) -> Vec<(usize, usize)> {
+        let (lo, hi) = self.game.layer_range(n);
+        let len = hi - lo;
+        let chunk = (len + self.threads - 1) / self.threads;
+        (0..self.threads).map(|t| (lo + min(len, t * chunk),
+                                   lo + min(len, (t + 1) * chunk)))
+                         .collect()
+    }
+
+    /// Run `f` on every state of the layer `n` in parallel.
+    fn for_layer<F>(&self, n: usize, f: F)
+            where F: Fn(usize, G::State) + Sync {
+        let (game, f) = (&self.game, &f);
+        crossbeam::scope(|scope| {
+            for (a, b) in self.ranges(n) {
+                scope.spawn(move || {
+                    for (c, u) in game.iter_range(n, a, b) {
+                        f(c, u);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Update the given state with the final score of one of its successors.
+    /// Propagate it recursively whenever it flips the state to a final score.
+    /// Return the number of states flipped.
+    fn propagate(&self, u: G::State, c: usize, up: i8, sat_lvl: i8) -> usize {
+        let mut flipped = 0;
+        let mut stack = vec![(u, c, up)];
+        while let Some((u, c, a)) = stack.pop() {
+            if let Some(b) = modify(self.table.atomic(c), |s| s.update(a, sat_lvl)) {
+                debug_assert!(-sat_lvl <= b && b <= sat_lvl);
+                flipped += 1;
+                // if update changed to final value, propagate further
+                for (v, d, _) in self.game.predecessors(&u) {
+                    stack.push((v, d, b));
+                }
+            }
+        }
+        return flipped;
+    }
+}
+
+
+impl<G: Game + Sync, T: ParTable> Solver for ParDriver<G, T> {
+    type Game = G;
+    type Table = T;
+    type Steps = ();
+
+    fn game(&self) -> &G {
+        &self.game
+    }
+
+    fn table(&self) -> &T {
+        &self.table
+    }
+
+    fn table_mut(&mut self) -> &mut T {
+        &mut self.table
+    }
+
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    fn checkpoint(&mut self) -> Option<&mut Checkpoint> {
+        self.checkpoint.as_mut()
+    }
+
+    fn initialize(&mut self, n: usize) {
+        let max_score = self.game.max_score(n);
+        self.for_layer(n, |c, u| {
+            let (score, nsucc, _) = initial_score(&self.game, &u, max_score, |d| {
+                State::unpack(self.table.atomic(d).load(Ordering::Acquire)).val
+            });
+            self.table.atomic(c).store(State::new(score, nsucc).pack(), Ordering::Release);
+        });
+    }
+
+    fn step(&mut self, n: usize, sat_lvl: i8, _: &mut ()) -> usize {
+        let flipped = AtomicUsize::new(0);
+        self.for_layer(n, |c, u| {
+            if let Some(x) = modify(self.table.atomic(c), |s| s.try_stabilize(sat_lvl)) {
+                debug_assert!(-sat_lvl <= x && x <= sat_lvl);
+                let mut k = 1;
+                for (v, d, _) in self.game.predecessors(&u) {
+                    k += self.propagate(v, d, x, sat_lvl);
+                }
+                flipped.fetch_add(k, Ordering::Relaxed);
+            }
+        });
+        flipped.load(Ordering::Relaxed)
+    }
+
+    fn zero_cycles(&mut self, n: usize) {
+        self.for_layer(n, |c, _| {
+            modify(self.table.atomic(c), |s| s.try_stabilize(0));
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use rules::{Rules,Forbidden,AllButLast};
+    use ra::{NaiveRAM,Solver,solve};
+    use super::ParDriver;
+
+    fn same_table<R: Rules + Sync>(geo: Geometry, rules: R) -> bool {
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: geo, rules: rules };
+        let seq = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        let mut par = ParDriver { game: game,
+                                  table: NaiveRAM::new(game.len(), log.clone()),
+                                  logger: log,
+                                  threads: 4,
+                                  checkpoint: None };
+        par.run();
+        (0..game.len()).all(|c| seq[c] == par.table[c])
+    }
+
+    /// Same as `ra::tests::unstable_count` with the workers.
+    #[test]
+    fn par_unstable_counter() {
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 9), rules: AllButLast };
+        let mut driver = ParDriver { game: game,
+                                     table: NaiveRAM::new(game.len(), log.clone()),
+                                     logger: log,
+                                     threads: 3,
+                                     checkpoint: None };
+        for n in game.layers() {
+            let count = |t: &NaiveRAM| game.iter_layer(n).filter(|&(c, _)| t[c].nsuc.is_some())
+                                                          .count();
+            driver.initialize(n);
+            let mut unstable = count(&driver.table);
+            let max_score = game.max_score(n);
+            for l in 0..max_score {
+                unstable -= driver.step(n, max_score - l, &mut ());
+                assert_eq!(unstable, count(&driver.table), "layer {} step {}", n, l);
+            }
+            driver.zero_cycles(n);
+        }
+    }
+
+    #[test]
+    fn par_same_as_seq() {
+        assert!(same_table(Geometry::new(3, 10), Forbidden));
+        assert!(same_table(Geometry::new(4, 8), AllButLast));
+    }
+}
@@ -1,20 +1,25 @@
 use std::cmp::max;
 use std::option::Option;
-use std::ops::IndexMut;
+use std::mem;
+use std::io;
 
 use core::nonzero::NonZero;
 use slog::Logger;
 
-use SEEDS;
-use awari::Awari;
+use game::Game;
+use checkpoint::Checkpoint;
+use best::BestMoves;
 
-pub use ::storage::{NaiveRAM,MMaped};
+pub use ::storage::{NaiveRAM,MMaped,Paged,Layered};
 
 
-#[derive(Eq,PartialEq)]
+/// Score of a board, together with the number of successors left to wait for
+/// (plus one) while it is not stable.
+#[derive(Copy,Clone,Eq,PartialEq)]
+#[repr(C, align(2))]
 pub struct State {
-    val: i8,
-    nsuc: Option<NonZero<u8>>,
+    pub val: i8,
+    pub nsuc: Option<NonZero<u8>>,
 }
 
 impl State {
@@ -25,6 +30,17 @@ impl State {
         }
     }
 
+    /// Pack the state in an integer (with the same memory layout).
+    #[inline]
+    pub fn pack(self) -> u16 {
+        unsafe { mem::transmute(self) }
+    }
+
+    #[inline]
+    pub fn unpack(x: u16) -> Self {
+        unsafe { mem::transmute(x) }
+    }
+
     /// Update the value of a board using the final value `up` from a successor.
     /// If it flipped the board to a stable state return the final value of the
     /// board, else return `None`.
@@ -34,14 +50,15 @@ impl State {
 
         match self.nsuc {
             Option::None => {
-                debug_assert!(self.val >= up);
+                debug_assert!(self.val >= -up);
                 Option::None
             },
             Option::Some(n) => {
                 debug_assert!(n.get() >= 2);
                 if n.get() == 2 {
                     self.nsuc = Option::None;
-                    Option::Some(max(self.val, -up))
+                    self.val = max(self.val, -up);
+                    Option::Some(self.val)
                 } else if self.val == sat_lvl || -up == sat_lvl {
                     self.nsuc = Option::None;
                     self.val = sat_lvl;
@@ -85,93 +102,588 @@ impl State {
 }
 
 
-pub trait Table: IndexMut<usize, Output=State> {
+/// Storage of the states of a game. Reads copy the states out, so a table may
+/// drop anything it keeps in memory on every access.
+pub trait Table {
+    fn get(&self, i: usize) -> State;
+    fn get_mut(&mut self, i: usize) -> &mut State;
     fn insert(&mut self, usize, State);
     fn pre_hook(&mut self, usize);
     fn post_hook(&mut self, usize);
     fn finish_hook(&mut self);
+    /// Make sure every state written so far is persisted.
+    fn flush(&mut self);
+}
+
+
+/// Read-only access to the states of a finished table or database.
+pub trait Scores {
+    fn state(&self, i: usize) -> State;
+}
+
+impl<T: Table> Scores for T {
+    #[inline]
+    fn state(&self, i: usize) -> State {
+        self.get(i)
+    }
 }
 
+/// Initial score of the board `u` in a layer whose scores are at most
+/// `max_score`: the best score of its moves leaving the layer (the final
+/// scores of their successors being given by `val`), or its terminal score.
+/// Also return the number of its successors in the layer, which it has to
+/// wait for, and the move (pit) to its best successor out of the layer.
+#[inline]
+pub fn initial_score<G, F>(game: &G, u: &G::State, max_score: i8, val: F) -> (i8, u8, Option<usize>)
+        where G: Game, F: Fn(usize) -> i8 {
+    let (mut score, mut nsucc, mut term) = (-max_score, 0, true);
+    let mut arg = None;
+    for (_, k, d, again, i) in game.successors(u) {
+        term = false;
+        let x = if again {
+            debug_assert!(k > 0);
+            k as i8 + val(d)
+        } else if k > 0 {
+            k as i8 - val(d)
+        } else {
+            nsucc += 1;
+            continue;
+        };
+        if arg.is_none() || x > score {
+            score = max(score, x);
+            arg = Some(i);
+        }
+    }
+    if term {
+        score = game.terminal_score(u);
+    }
+    (score, nsucc, arg)
+}
+
+
+/// Retrograde analysis of a game, layer by layer. The drivers implement how
+/// the states of a layer are visited (`Driver` one by one, `par::ParDriver`
+/// with several workers), the provided methods do the rest of a run: order
+/// of the layers and of the saturation levels, early exit, checkpoints and
+/// table hooks.
+pub trait Solver {
+    type Game: Game;
+    type Table: Table;
+    /// What the driver keeps between the steps of a layer.
+    type Steps;
+
+    fn game(&self) -> &Self::Game;
+    fn table(&self) -> &Self::Table;
+    fn table_mut(&mut self) -> &mut Self::Table;
+    fn logger(&self) -> &Logger;
+    fn checkpoint(&mut self) -> Option<&mut Checkpoint>;
+
+    /// Set the initial state of every board of layer `n`.
+    fn initialize(&mut self, n: usize) -> Self::Steps;
+
+    /// Run the step of level `sat_lvl` on layer `n` and return the number of
+    /// states flipped.
+    fn step(&mut self, n: usize, sat_lvl: i8, steps: &mut Self::Steps) -> usize;
+
+    /// Set the score of every instable board of layer `n` to 0.
+    fn zero_cycles(&mut self, n: usize);
+
+    /// Solve the layer `n`, only running every other saturation level if
+    /// `parity` (the scores of the previous layers have the parity of their
+    /// maximum). Return whether the scores of this layer have it as well.
+    fn iteration(&mut self, n: usize, parity: bool) -> bool {
+        info!(self.logger(), "iter start"; "iter" => n);
+        self.table_mut().pre_hook(n);
+
+        info!(self.logger(), "initialization"; "iter" => n);
+        let max_score = self.game().max_score(n);
+        let mut steps = self.initialize(n);
+
+        info!(self.logger(), "convergence"; "iter" => n);
+        // when the scores don't have the parity of the layer (seeds split at
+        // the end, captures leading to a cycle), we need every level
+        let parity = parity && self.game().same_parity(n);
+        let delta = if parity { 2 } else { 1 };
+        let (lo, hi) = self.game().layer_range(n);
+        let mut unstable = hi - lo;
+        for l in 0..(max_score + delta - 1) / delta {
+            if unstable == 0 {
+                info!(self.logger(), "early exit"; "iter" => n, "step" => l);
+                break;
+            }
+            info!(self.logger(), "step start"; "iter" => n, "step" => l);
+            unstable -= self.step(n, max_score - delta * l, &mut steps);
+            info!(self.logger(), "step end"; "iter" => n, "step" => l, "unstable" => unstable);
+            if let Some(ckpt) = self.checkpoint() {
+                ckpt.step_done(n, l as usize + 1).unwrap();
+            }
+        }
+        // the remaining instable boards are in a cycle
+        if unstable > 0 {
+            info!(self.logger(), "step start"; "iter" => n, "step" => max_score);
+            self.zero_cycles(n);
+        }
+        self.table_mut().post_hook(n);
+        self.layer_done(n);
+        info!(self.logger(), "iter end"; "iter" => n);
+        parity && (unstable == 0 || max_score % 2 == 0)
+    }
+
+    /// Test if the scores of the solved layer `n` have the parity of its
+    /// maximum.
+    fn kept_parity(&self, n: usize) -> bool {
+        let max_score = self.game().max_score(n);
+        self.game().iter_layer(n).all(|(c, _)| (self.table().get(c).val - max_score) % 2 == 0)
+    }
 
-pub struct Driver<T: Table> {
+    /// Persist the table and record that layer `n` is done.
+    fn layer_done(&mut self, n: usize) {
+        if self.checkpoint().is_some() {
+            self.table_mut().flush();
+            self.checkpoint().unwrap().layer_done(n).unwrap();
+        }
+    }
+
+    /// Construct the optimal score table! Yay!
+    fn run(&mut self) {
+        self.run_after(None);
+    }
+
+    /// Continue the run recorded in the checkpoint, restarting from the first
+    /// layer that was not done.
+    fn resume(&mut self) -> io::Result<()> {
+        let done = match self.checkpoint() {
+            Some(ckpt) => ckpt.load()?.map(|p| p.done),
+            None => None,
+        };
+        info!(self.logger(), "resume"; "done" => done);
+        self.run_after(done);
+        Ok(())
+    }
+
+    /// Compute every layer after `done` (all of them if `None`).
+    fn run_after(&mut self, done: Option<usize>) {
+        let layers = self.game().layers();
+        let start = done.map_or(0, |d| {
+            layers.iter().position(|&n| n == d).expect("unknown layer") + 1
+        });
+        let mut parity = layers[..start].iter().all(|&n| self.kept_parity(n));
+        for &n in &layers[start..] {
+            parity = self.iteration(n, parity);
+        }
+
+        self.table_mut().finish_hook();
+    }
+}
+
+
+/// Sequential driver.
+pub struct Driver<G: Game, T: Table> {
+    pub game: G,
     pub table: T,
     pub logger: Logger,
+    pub checkpoint: Option<Checkpoint>,
+    /// If set, record an optimal move of every board.
+    pub best: Option<BestMoves>,
+    /// If set, keep the boards that may stabilize at each step in buckets
+    /// instead of scanning the whole layer at every step (uses more memory).
+    pub buckets: bool,
+}
+
+
+/// Solve `game` in `table` with a silent logger, for the tests.
+#[cfg(test)]
+pub fn solve<G: Game, T: Table>(game: G, table: T) -> T {
+    let mut driver = Driver::new(game, table, Logger::root(::slog::Discard, o!()));
+    driver.run();
+    driver.table
 }
 
 
-impl<T: Table> Driver<T> {
-    /// Update the given state with the final score of one of its successors.
-    /// Propagate it recursively whenever it flips the state to a final score.
-    fn propagate(&mut self, u: Awari, up: i8, sat_lvl: i8) {
-        let mut stack = vec![(u, up)];
-        while let Some((u, a)) = stack.pop() {
-            if let Some(b) = self.table[u.encode()].update(a, sat_lvl) {
+impl<G: Game, T: Table> Driver<G, T> {
+    /// Driver scanning the layers, without checkpoint nor best moves.
+    pub fn new(game: G, table: T, logger: Logger) -> Self {
+        Driver { game: game,
+                 table: table,
+                 logger: logger,
+                 checkpoint: None,
+                 best: None,
+                 buckets: false }
+    }
+
+    /// Update the given state with the final score of one of its successors
+    /// (reached by playing `pit`). Propagate it recursively whenever it flips
+    /// the state to a final score. States whose score is raised are pushed in
+    /// the bucket of their new score (if there is one). Return the number of
+    /// states flipped.
+    fn propagate(&mut self, u: G::State, c: usize, up: i8, pit: usize, sat_lvl: i8,
+                 lvls: &mut [Vec<usize>]) -> usize {
+        let mut flipped = 0;
+        let mut stack = vec![(u, c, up, pit)];
+        while let Some((u, c, a, i)) = stack.pop() {
+            let (open, old, r) = {
+                let s = self.table.get_mut(c);
+                (s.nsuc.is_some(), s.val, s.update(a, sat_lvl))
+            };
+            if let Some(ref mut best) = self.best {
+                // the successor reaches the current score of the state
+                if open && -a >= old {
+                    best.record(c, i);
+                }
+            }
+            if let Some(b) = r {
                 debug_assert!(-sat_lvl <= b && b <= sat_lvl);
+                flipped += 1;
                 // if update changed to final value, propagate further
-                for v in u.predecessors() {
-                    stack.push((v, b));
+                for (v, d, i) in self.game.predecessors(&u) {
+                    stack.push((v, d, b, i));
                 }
+            } else if open && -a > max(old, 0) && ((-a) as usize) < lvls.len() {
+                lvls[(-a) as usize].push(c);
             }
         }
+        return flipped;
     }
 
-    fn iteration(&mut self, n: usize) {
-        info!(self.logger, "iter start"; "iter" => n);
-        self.table.pre_hook(n);
+    /// Try to flip the given state to a final score at the step of level
+    /// `sat_lvl` and propagate it. Return the number of states flipped.
+    #[inline]
+    fn stabilize(&mut self, u: G::State, c: usize, sat_lvl: i8,
+                 lvls: &mut [Vec<usize>]) -> usize {
+        if let Some(x) = self.table.get_mut(c).try_stabilize(sat_lvl) {
+            debug_assert!(-sat_lvl <= x && x <= sat_lvl);
+            let mut flipped = 1;
+            for (v, d, i) in self.game.predecessors(&u) {
+                flipped += self.propagate(v, d, x, i, sat_lvl, lvls);
+            }
+            flipped
+        } else {
+            0
+        }
+    }
 
-        info!(self.logger, "initialization"; "iter" => n);
-        for (c, u) in Awari::iter_config(n) {
-            let (mut score, mut nsucc) = (-(n as i8), 0);
-            for (v, k) in u.successors() {
-                if k > 0 {
-                    score = max(score, k as i8 - self.table[v.encode()].val);
-                }
-                nsucc += 1;
+    /// Extend a table solved for `old` (the same game with less seeds) to the
+    /// current game. The table must already have the new size, starting with
+    /// the old table. The layers of `old` are moved to their new place and
+    /// only the missing layers are computed (for awari, this includes the
+    /// SEEDS-1 layer of `old`, which is not accessible there).
+    ///
+    /// Once the layers are moved, it is recorded in the checkpoint, so an
+    /// interrupted extension can be continued by running it again: the layers
+    /// are not moved twice and only the new layers that were not done are
+    /// computed. An extension interrupted while moving the layers must start
+    /// again from the old table.
+    pub fn extend(&mut self, old: &G) {
+        let layers = self.game.layers();
+        let olds = old.layers();
+        assert!(olds.iter().all(|n| layers.contains(n)), "not a smaller game");
+        info!(self.logger, "extend"; "from" => old.name(), "to" => self.game.name());
+
+        // the manifest of the smaller run is of another variant
+        let progress = self.checkpoint.as_mut().and_then(|c| c.load().unwrap_or(None));
+        let done = match progress {
+            Some(ref p) if p.moved => {
+                info!(self.logger, "layers already moved"; "done" => p.done);
+                layers.iter().position(|&n| n == p.done)
+            },
+            _ => {
+                self.move_layers(old);
+                // everything before the first new layer is done
+                layers.iter().position(|n| !olds.contains(n)).and_then(|i| i.checked_sub(1))
+            },
+        };
+        if let Some(i) = done {
+            self.layer_done(layers[i]);
+        }
+
+        let mut parity = true;
+        for (i, &n) in layers.iter().enumerate() {
+            parity = if olds.contains(&n) || done.map_or(false, |d| i <= d) {
+                parity && self.kept_parity(n)
+            } else {
+                self.iteration(n, parity)
+            };
+        }
+
+        self.table.finish_hook();
+    }
+
+    /// Move the layers of `old` to their place in the current game and record
+    /// it in the checkpoint.
+    fn move_layers(&mut self, old: &G) {
+        // layers only move up, so start from the last ones
+        let mut moves: Vec<_> = old.layers().into_iter().map(|n| {
+            (old.layer_range(n), self.game.layer_range(n))
+        }).collect();
+        moves.sort_by(|x, y| (y.0).0.cmp(&(x.0).0));
+        for ((a, b), (c, d)) in moves {
+            assert!(b - a == d - c && a <= c);
+            for i in (0..b-a).rev() {
+                let s = self.table.get(a + i);
+                self.table.insert(c + i, s);
             }
+        }
+        if self.checkpoint.is_some() {
+            self.table.flush();
+            self.checkpoint.as_mut().unwrap().layers_moved().unwrap();
+        }
+    }
+}
+
+
+impl<G: Game, T: Table> Solver for Driver<G, T> {
+    type Game = G;
+    type Table = T;
+    /// lvls[x]: boards that may stabilize at the step of level x (with
+    /// `buckets`)
+    type Steps = Vec<Vec<usize>>;
+
+    fn game(&self) -> &G {
+        &self.game
+    }
+
+    fn table(&self) -> &T {
+        &self.table
+    }
+
+    fn table_mut(&mut self) -> &mut T {
+        &mut self.table
+    }
+
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    fn checkpoint(&mut self) -> Option<&mut Checkpoint> {
+        self.checkpoint.as_mut()
+    }
+
+    fn initialize(&mut self, n: usize) -> Vec<Vec<usize>> {
+        let max_score = self.game.max_score(n);
+        let mut lvls = vec![Vec::new(); if self.buckets { max_score as usize + 1 } else { 0 }];
+        for (c, u) in self.game.iter_layer(n) {
+            // only wait for the successors in the current layer, the others
+            // are already stable
+            let (score, nsucc, arg) = {
+                let table = &self.table;
+                initial_score(&self.game, &u, max_score, |d| table.get(d).val)
+            };
             self.table.insert(c, State::new(score, nsucc));
+            if self.buckets {
+                if nsucc == 0 {
+                    lvls[max_score as usize].push(c);
+                } else if score > 0 {
+                    lvls[score as usize].push(c);
+                }
+            }
+            if let (Some(best), Some(i)) = (self.best.as_mut(), arg) {
+                best.record(c, i);
+            }
         }
+        return lvls;
+    }
 
-        info!(self.logger, "convergence"; "iter" => n);
-        for l in 0..(n+1)/2 {
-            info!(self.logger, "step start"; "iter" => n, "step" => 2*l);
-            let sat_lvl = (n - 2*l) as i8;
-            for (c, u) in Awari::iter_config(n) {
-                // yup, temporary lifetimes have struck again..
-                if let Some(x) = { let ref mut tmp = self.table[c];
-                                   tmp.try_stabilize(sat_lvl) } {
-                    debug_assert!(-sat_lvl <= x && x <= sat_lvl);
-                    for v in u.predecessors() {
-                        self.propagate(v, x, sat_lvl);
-                    }
+    fn step(&mut self, n: usize, sat_lvl: i8, lvls: &mut Vec<Vec<usize>>) -> usize {
+        let mut flipped = 0;
+        if self.buckets {
+            let mut cs = mem::replace(&mut lvls[sat_lvl as usize], Vec::new());
+            cs.sort();
+            cs.dedup();
+            for c in cs {
+                let u = self.game.iter_range(n, c, c + 1).next().unwrap().1;
+                flipped += self.stabilize(u, c, sat_lvl, lvls);
+            }
+        } else {
+            for (c, u) in self.game.iter_layer(n) {
+                flipped += self.stabilize(u, c, sat_lvl, lvls);
+            }
+        }
+        return flipped;
+    }
+
+    /// Set the score of every instable board of layer `n` to 0.
+    fn zero_cycles(&mut self, n: usize) {
+        let mut cyclic = Vec::new();
+        for (c, u) in self.game.iter_layer(n) {
+            let tmp = self.table.get_mut(c);
+            if tmp.nsuc != Option::None {
+                tmp.val = 0;
+                tmp.nsuc = Option::None;
+                if self.best.is_some() {
+                    cyclic.push((c, u));
                 }
             }
         }
-        if n & 1 == 0 {
-            info!(self.logger, "step start"; "iter" => n, "step" => n);
-            for (c, _) in Awari::iter_config(n) {
-                let ref mut tmp = self.table[c];
-                if tmp.nsuc != Option::None {
-                    tmp.val = 0;
-                    tmp.nsuc = Option::None;
+        // their move must stay in the cycle (or reach any other 0), there is
+        // none for terminal boards of a layer with no step
+        for (c, u) in cyclic {
+            let m = self.game.successors(&u).find(|&(_, k, d, again, _)| {
+                let y = self.table.get(d).val;
+                (if again { k as i8 + y } else { k as i8 - y }) == 0
+            });
+            if let Some((_, _, _, _, i)) = m {
+                self.best.as_mut().unwrap().record(c, i);
+            }
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::max;
+    use std::env;
+    use std::fs::{create_dir_all,remove_dir_all};
+    use test::Bencher;
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::{Awari,AwariGame};
+    use kalah::KalahGame;
+    use rules::{Rules,Variant,Ending,Forbidden,NoCapture,CaptureAll,AllButLast};
+    use checkpoint::Checkpoint;
+    use super::{Driver,NaiveRAM,State,Table,Solver,solve};
+
+    fn ram<G: Game>(game: G) -> NaiveRAM {
+        solve(game, NaiveRAM::new(game.len(), Logger::root(Discard, o!())))
+    }
+
+    /// Solve `geo` and return the codes of the boards whose score isn't the
+    /// best of their moves (or their terminal score).
+    fn minimax_errors<R: Rules>(geo: Geometry, rules: R) -> Vec<usize> {
+        let table = ram(AwariGame { geo: geo, rules: rules });
+        let mut errors = Vec::new();
+        for n in (1..geo.seeds-1).chain(Some(geo.seeds)) {
+            for (c, u) in Awari::iter_config(geo, n) {
+                let s = &table[c];
+                let best = u.successors(rules).into_iter()
+                            .map(|(v, k)| k as i8 - table[v.encode()].val)
+                            .fold(None, |b: Option<i8>, x| Some(b.map_or(x, |b| max(b, x))));
+                if s.nsuc.is_some() || s.val != best.unwrap_or(rules.terminal_score(&u)) {
+                    errors.push(c);
                 }
             }
         }
-        info!(self.logger, "iter end"; "iter" => n);
+        errors
     }
 
-    /// Construct the optimal score table! Yay!
-    pub fn run(&mut self) {
-        // first iteration
-        self.table.pre_hook(0);
-        self.table.insert(0, State { val: 0, nsuc: Option::None });
-        self.table.post_hook(0);
+    #[test]
+    fn minimax() {
+        for &(pits, seeds) in &[(2, 8), (3, 8), (3, 11), (4, 7)] {
+            let geo = Geometry::new(pits, seeds);
+            for &e in &[Ending::Opponent, Ending::Owner, Ending::Split] {
+                assert_eq!(minimax_errors(geo, Variant { slam: Forbidden, ending: e }), vec![]);
+                assert_eq!(minimax_errors(geo, Variant { slam: NoCapture, ending: e }), vec![]);
+                assert_eq!(minimax_errors(geo, Variant { slam: CaptureAll, ending: e }), vec![]);
+                assert_eq!(minimax_errors(geo, Variant { slam: AllButLast, ending: e }), vec![]);
+            }
+        }
+    }
+
+    fn with_buckets<G: Game>(game: G) -> NaiveRAM {
+        let log = Logger::root(Discard, o!());
+        let mut driver = Driver { buckets: true,
+                                  ..Driver::new(game, NaiveRAM::new(game.len(), log.clone()), log) };
+        driver.run();
+        driver.table
+    }
+
+    fn same_table<G: Game>(game: G) -> bool {
+        let (a, b) = (ram(game), with_buckets(game));
+        (0..game.len()).all(|c| a[c] == b[c])
+    }
+
+    /// Solve `small` then extend its table to `big`, twice if `again`
+    /// (with a checkpoint, as after an interrupted extension).
+    fn extended<G: Game>(small: G, big: G, again: bool) -> NaiveRAM {
+        let log = Logger::root(Discard, o!());
+        let old = ram(small);
+        let mut table = NaiveRAM::new(big.len(), log.clone());
+        for c in 0..small.len() {
+            table.insert(c, old[c]);
+        }
+        let mut driver = Driver::new(big, table, log);
+        if again {
+            let wd = env::temp_dir().join(format!("awari-extend-test-{}", big.name()));
+            let _ = remove_dir_all(&wd);
+            create_dir_all(&wd).unwrap();
+            driver.checkpoint = Some(Checkpoint::new(&wd, &big));
+            driver.extend(&small);
+            driver.extend(&small);
+            remove_dir_all(&wd).unwrap();
+        } else {
+            driver.extend(&small);
+        }
+        driver.table
+    }
 
-        // don't compute the second to last iteration
-        for n in 1..SEEDS-1 {
-            self.iteration(n);
+    #[test]
+    fn extend_same_as_run() {
+        let rules = Variant { slam: AllButLast, ending: Ending::Split };
+        let big = AwariGame { geo: Geometry::new(3, 11), rules: rules };
+        let small = AwariGame { geo: Geometry::new(3, 7), rules: rules };
+        let a = ram(big);
+        for &again in &[false, true] {
+            let b = extended(small, big, again);
+            assert!((0..big.len()).all(|c| a[c] == b[c]));
         }
-        self.iteration(SEEDS);
 
-        self.table.finish_hook();
+        let big = KalahGame { geo: Geometry::new(3, 9) };
+        let small = KalahGame { geo: Geometry::new(3, 5) };
+        let a = ram(big);
+        for &again in &[false, true] {
+            let b = extended(small, big, again);
+            assert!((0..big.len()).all(|c| a[c] == b[c]));
+        }
+    }
+
+    /// Check that the count of unstable states of every layer matches the
+    /// table after each step, so it reaches 0 exactly when the layer is done.
+    fn unstable_count<G: Game>(game: G, buckets: bool) {
+        let log = Logger::root(Discard, o!());
+        let mut driver = Driver { buckets: buckets,
+                                  ..Driver::new(game, NaiveRAM::new(game.len(), log.clone()), log) };
+        for n in game.layers() {
+            let count = |t: &NaiveRAM| game.iter_layer(n).filter(|&(c, _)| t[c].nsuc.is_some())
+                                                          .count();
+            let mut lvls = driver.initialize(n);
+            let mut unstable = count(&driver.table);
+            let max_score = game.max_score(n);
+            for l in 0..max_score {
+                unstable -= driver.step(n, max_score - l, &mut lvls);
+                assert_eq!(unstable, count(&driver.table), "layer {} step {}", n, l);
+            }
+            driver.zero_cycles(n);
+        }
+    }
+
+    #[test]
+    fn unstable_counter() {
+        let rules = Variant { slam: AllButLast, ending: Ending::Split };
+        unstable_count(AwariGame { geo: Geometry::new(3, 9), rules: rules }, false);
+        unstable_count(AwariGame { geo: Geometry::new(3, 9), rules: rules }, true);
+        unstable_count(KalahGame { geo: Geometry::new(3, 9) }, false);
+    }
+
+    #[test]
+    fn buckets_same_as_scan() {
+        assert!(same_table(AwariGame { geo: Geometry::new(3, 10),
+                                       rules: Variant { slam: AllButLast,
+                                                        ending: Ending::Split } }));
+        assert!(same_table(KalahGame { geo: Geometry::new(3, 10) }));
+    }
+
+    #[bench]
+    fn bench_solve_scan(b: &mut Bencher) {
+        let game = AwariGame { geo: Geometry::new(4, 8), rules: AllButLast };
+        b.iter(|| ram(game));
+    }
+
+    #[bench]
+    fn bench_solve_buckets(b: &mut Bencher) {
+        let game = AwariGame { geo: Geometry::new(4, 8), rules: AllButLast };
+        b.iter(|| with_buckets(game));
     }
 }
@@ -0,0 +1,239 @@
+use std::cmp::min;
+
+use game::Game;
+use bitvec::{BitVec,RankSelect};
+
+
+/// Explore the states reachable from the start of `game` and index their
+/// codes.
+pub fn explore<G: Game>(game: &G) -> RankSelect {
+    let mut bits = BitVec::new(game.len());
+    // the start itself may have no code (awari)
+    let start = game.start();
+    if game.has_code(&start) {
+        bits.set(game.encode(&start), true);
+    }
+    let mut stack = vec![start];
+    while let Some(u) = stack.pop() {
+        for (v, _, d, _, _) in game.successors(&u) {
+            if !bits.get(d) {
+                bits.set(d, true);
+                stack.push(v);
+            }
+        }
+    }
+    RankSelect::new(bits)
+}
+
+
+/// Restriction of a game to the states reachable from its start, addressed by
+/// their rank in the set computed by `explore`. Solving it only needs a table for the
+/// reachable states.
+#[derive(Copy, Clone)]
+pub struct Reachable<'a, G: Game> {
+    pub game: G,
+    pub set: &'a RankSelect,
+}
+
+
+impl<'a, G: Game> Reachable<'a, G> {
+    pub fn new(game: G, set: &'a RankSelect) -> Self {
+        Reachable { game: game, set: set }
+    }
+}
+
+
+pub struct Iter<'a, G: Game> {
+    inner: G::Iter,
+    set: &'a RankSelect,
+}
+
+impl<'a, G: Game> Iterator for Iter<'a, G> {
+    type Item = (usize, G::State);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, G::State)> {
+        while let Some((c, u)) = self.inner.next() {
+            if self.set.get(c) {
+                return Some((self.set.rank(c), u));
+            }
+        }
+        return None;
+    }
+}
+
+
+pub struct Successors<'a, G: Game> {
+    inner: G::Successors,
+    set: &'a RankSelect,
+}
+
+impl<'a, G: Game> Iterator for Successors<'a, G> {
+    type Item = (G::State, u8, usize, bool, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(G::State, u8, usize, bool, usize)> {
+        self.inner.next().map(|(v, k, d, again, i)| (v, k, self.set.rank(d), again, i))
+    }
+}
+
+
+/// Predecessors of a reachable state that are reachable.
+pub struct Predecessors<'a, G: Game> {
+    inner: G::Predecessors,
+    set: &'a RankSelect,
+}
+
+impl<'a, G: Game> Iterator for Predecessors<'a, G> {
+    type Item = (G::State, usize, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(G::State, usize, usize)> {
+        while let Some((v, d, i)) = self.inner.next() {
+            if self.set.get(d) {
+                return Some((v, self.set.rank(d), i));
+            }
+        }
+        return None;
+    }
+}
+
+
+impl<'a, G: Game> Game for Reachable<'a, G> {
+    type State = G::State;
+    type Iter = Iter<'a, G>;
+    type Successors = Successors<'a, G>;
+    type Predecessors = Predecessors<'a, G>;
+
+    fn name(&self) -> String {
+        format!("{} reachable", self.game.name())
+    }
+
+    fn len(&self) -> usize {
+        self.set.ones()
+    }
+
+    fn layers(&self) -> Vec<usize> {
+        self.game.layers()
+    }
+
+    fn layer_range(&self, n: usize) -> (usize, usize) {
+        let (a, b) = self.game.layer_range(n);
+        (self.set.rank(a), self.set.rank(b))
+    }
+
+    fn iter_range(&self, n: usize, start: usize, end: usize) -> Iter<'a, G> {
+        let b = self.game.layer_range(n).1;
+        let (s, e) = (min(self.set.select(start), b), min(self.set.select(end), b));
+        Iter { inner: self.game.iter_range(n, s, e), set: self.set }
+    }
+
+    fn max_score(&self, n: usize) -> i8 {
+        self.game.max_score(n)
+    }
+
+    fn same_parity(&self, n: usize) -> bool {
+        self.game.same_parity(n)
+    }
+
+    #[inline]
+    fn encode(&self, u: &G::State) -> usize {
+        let c = self.game.encode(u);
+        debug_assert!(self.set.get(c), "unreachable state");
+        self.set.rank(c)
+    }
+
+    fn has_code(&self, u: &G::State) -> bool {
+        self.game.has_code(u) && self.set.get(self.game.encode(u))
+    }
+
+    fn start(&self) -> G::State {
+        self.game.start()
+    }
+
+    #[inline]
+    fn successors(&self, u: &G::State) -> Successors<'a, G> {
+        Successors { inner: self.game.successors(u), set: self.set }
+    }
+
+    fn play(&self, u: &G::State, i: usize) -> Option<(G::State, u8, usize, bool)> {
+        self.game.play(u, i).map(|(v, k, d, again)| (v, k, self.set.rank(d), again))
+    }
+
+    #[inline]
+    fn predecessors(&self, u: &G::State) -> Predecessors<'a, G> {
+        Predecessors { inner: self.game.predecessors(u), set: self.set }
+    }
+
+    fn terminal_score(&self, u: &G::State) -> i8 {
+        self.game.terminal_score(u)
+    }
+
+    fn index(&self) -> Option<&RankSelect> {
+        Some(self.set)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use kalah::KalahGame;
+    use rules::{Variant,Ending,AllButLast};
+    use ra::{NaiveRAM,solve};
+    use super::{explore,Reachable};
+
+    /// Check the rank index and that solving the reachable states gives the
+    /// scores of the full table.
+    fn same_scores<G: Game>(game: G) {
+        let set = explore(&game);
+        assert!(set.ones() < game.len());
+        let mut i = 0;
+        for c in 0..game.len() {
+            assert_eq!(set.rank(c), i);
+            if set.get(c) {
+                assert_eq!(set.select(i), c);
+                i += 1;
+            }
+        }
+        assert_eq!(set.select(i), game.len());
+
+        let reach = Reachable::new(game, &set);
+        let log = Logger::root(Discard, o!());
+        let (full, part) = (solve(game, NaiveRAM::new(game.len(), log.clone())),
+                            solve(reach, NaiveRAM::new(reach.len(), log)));
+        for c in 0..game.len() {
+            if set.get(c) {
+                assert!(full[c] == part[set.rank(c)], "code {}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn reachable_start() {
+        let game = KalahGame { geo: Geometry::new(3, 12) };
+        let set = explore(&game);
+        let reach = Reachable::new(game, &set);
+        let start = game.start();
+        assert!(reach.has_code(&start));
+        assert_eq!(set.select(reach.encode(&start)), game.encode(&start));
+
+        // the awari start has no code
+        let game = AwariGame { geo: Geometry::new(3, 12),
+                               rules: Variant { slam: AllButLast, ending: Ending::Owner } };
+        let set = explore(&game);
+        assert!(!game.has_code(&game.start()));
+        assert!(!Reachable::new(game, &set).has_code(&game.start()));
+    }
+
+    #[test]
+    fn reachable_scores() {
+        same_scores(AwariGame { geo: Geometry::new(3, 12),
+                                rules: Variant { slam: AllButLast, ending: Ending::Owner } });
+        same_scores(KalahGame { geo: Geometry::new(3, 12) });
+    }
+}
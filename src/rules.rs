@@ -0,0 +1,215 @@
+use awari::Awari;
+
+
+/// Attribution of the seeds left on the board when the player to move cannot
+/// play.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Ending {
+    /// The opponent takes every seed.
+    Opponent,
+    /// Each player takes the seeds on his side.
+    Owner,
+    /// The seeds are split evenly between the players.
+    Split,
+}
+
+impl Ending {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Ending::Opponent => "opponent",
+            Ending::Owner => "owner",
+            Ending::Split => "split",
+        }
+    }
+
+    /// Final score of a board where the player to move cannot play.
+    pub fn score(&self, u: &Awari) -> i8 {
+        let pits = u.geometry().pits;
+        let own = u[..pits].iter().fold(0, |a, &x| a + x as i8);
+        let opp = u[pits..].iter().fold(0, |a, &x| a + x as i8);
+        match *self {
+            Ending::Opponent => -(own + opp),
+            Ending::Owner => own - opp,
+            Ending::Split => 0,
+        }
+    }
+}
+
+
+/// Rule-set of the game, it decides what happens with a *grand slam*, that is
+/// a move that would capture every seed of the opponent, and who gets the
+/// seeds left at the end of the game.
+pub trait Rules: Copy {
+    /// Short name of the rule-set (used to name databases).
+    fn name(&self) -> &'static str;
+
+    /// Attribution of the seeds left at the end of the game.
+    fn ending(&self) -> Ending {
+        Ending::Opponent
+    }
+
+    /// Final score of a board where the player to move cannot play.
+    fn terminal_score(&self, u: &Awari) -> i8 {
+        self.ending().score(u)
+    }
+
+    /// Test if a grand slam is a legal move.
+    fn slam_legal(&self) -> bool;
+
+    /// Finish a grand slam whose last seed was sown in pit `j`: every opponent
+    /// pit up to `j` contains 2 or 3 seeds and the following ones are empty.
+    /// Return the number of captured seeds.
+    fn slam(&self, u: &mut Awari, j: usize) -> u8;
+}
+
+
+/// Capture the seeds of the opponent pits in range `[a,b)`.
+fn take(u: &mut Awari, a: usize, b: usize) -> u8 {
+    let mut n = 0;
+    for k in a..b {
+        n += u[k];
+        u[k] = 0;
+    }
+    return n;
+}
+
+
+/// Grand slams are illegal moves.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Forbidden;
+
+impl Rules for Forbidden {
+    fn name(&self) -> &'static str { "forbidden" }
+
+    fn slam_legal(&self) -> bool { false }
+
+    fn slam(&self, _: &mut Awari, _: usize) -> u8 {
+        unreachable!("grand slams are forbidden");
+    }
+}
+
+
+/// Grand slams are legal but capture nothing.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoCapture;
+
+impl Rules for NoCapture {
+    fn name(&self) -> &'static str { "nocapture" }
+
+    fn slam_legal(&self) -> bool { true }
+
+    fn slam(&self, _: &mut Awari, _: usize) -> u8 { 0 }
+}
+
+
+/// Grand slams capture every seed of the opponent.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CaptureAll;
+
+impl Rules for CaptureAll {
+    fn name(&self) -> &'static str { "captureall" }
+
+    fn slam_legal(&self) -> bool { true }
+
+    fn slam(&self, u: &mut Awari, j: usize) -> u8 {
+        let pits = u.geometry().pits;
+        take(u, pits, j + 1)
+    }
+}
+
+
+/// Grand slams capture every seed of the opponent but the ones of the pit where
+/// the last seed was sown.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AllButLast;
+
+impl Rules for AllButLast {
+    fn name(&self) -> &'static str { "allbutlast" }
+
+    fn slam_legal(&self) -> bool { true }
+
+    fn slam(&self, u: &mut Awari, j: usize) -> u8 {
+        let pits = u.geometry().pits;
+        take(u, pits, j)
+    }
+}
+
+
+/// Grand slam policy `R` with another end-of-game attribution.
+#[derive(Copy, Clone, Debug)]
+pub struct Variant<R: Rules> {
+    pub slam: R,
+    pub ending: Ending,
+}
+
+impl<R: Rules> Rules for Variant<R> {
+    fn name(&self) -> &'static str { self.slam.name() }
+
+    fn ending(&self) -> Ending { self.ending }
+
+    fn slam_legal(&self) -> bool { self.slam.slam_legal() }
+
+    fn slam(&self, u: &mut Awari, j: usize) -> u8 { self.slam.slam(u, j) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use utils::Geometry;
+    use awari::Awari;
+    use super::*;
+
+    const GEO: Geometry = Geometry { pits: 3, seeds: 12, full: false };
+
+    /// Board where playing the last pit is a grand slam capturing 5 seeds.
+    fn slam_board() -> Awari {
+        let mut u = Awari::empty(GEO);
+        u[0] = 1;
+        u[2] = 2;
+        u[3] = 1;
+        u[4] = 2;
+        return u;
+    }
+
+    /// Successors of `slam_board` obtained by playing its last pit.
+    fn slam_moves<R: Rules>(rules: R) -> Vec<(Awari, u8)> {
+        slam_board().successors(rules)
+                    .filter(|&(v, _)| v[5] == 0)
+                    .collect()
+    }
+
+    #[test]
+    fn slam_forbidden() {
+        assert!(slam_moves(Forbidden).is_empty());
+    }
+
+    #[test]
+    fn slam_nocapture() {
+        let v = slam_moves(NoCapture);
+        assert!(v.len() == 1 && v[0].1 == 0);
+    }
+
+    #[test]
+    fn slam_captureall() {
+        let v = slam_moves(CaptureAll);
+        assert!(v.len() == 1 && v[0].1 == 5);
+    }
+
+    #[test]
+    fn slam_allbutlast() {
+        let v = slam_moves(AllButLast);
+        assert!(v.len() == 1 && v[0].1 == 2);
+    }
+
+    #[test]
+    fn ending_scores() {
+        // the only move is a forbidden grand slam
+        let mut u = slam_board();
+        u[0] = 0;
+        let rules = |e| Variant { slam: Forbidden, ending: e };
+        assert!(u.successors(Forbidden).next().is_none());
+        assert_eq!(rules(Ending::Opponent).terminal_score(&u), -5);
+        assert_eq!(rules(Ending::Owner).terminal_score(&u), -1);
+        assert_eq!(rules(Ending::Split).terminal_score(&u), 0);
+    }
+}
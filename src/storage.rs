@@ -1,30 +1,39 @@
-use std::boxed::{Box,HEAP};
-use std::path::Path;
+use std::path::{Path,PathBuf};
 use std::convert::AsRef;
 use std::mem;
-use std::fs::OpenOptions;
+use std::cmp::{min,Ordering};
+use std::cell::RefCell;
+use std::collections::{HashMap,BTreeMap};
+use std::fs::{File,OpenOptions};
+use std::os::unix::fs::FileExt;
 use std::os::unix::io::AsRawFd;
 use std::io;
 use std::ops::{Index,IndexMut};
-use std::ptr;
+use std::{ptr,slice};
+use std::sync::atomic::AtomicU16;
 
 use libc;
 use slog::Logger;
 
-use NBOARDS;
+use game::Game;
 use ra::{State,Table};
+use par::ParTable;
 
 
+/// Table kept in memory. The states are stored as atomics (with the layout of
+/// `State`) so that the workers of a `ParDriver` may share it.
 pub struct NaiveRAM {
-    data: Box<[State; NBOARDS]>,
+    data: Box<[AtomicU16]>,
     logger: Logger,
 }
 
 
 impl NaiveRAM {
-    pub fn new(log: Logger) -> Self {
+    pub fn new(len: usize, log: Logger) -> Self {
+        // zeroed memory is only touched when the states are written
+        let data = vec![0u16; len].into_boxed_slice();
         NaiveRAM {
-            data: HEAP <- unsafe { mem::uninitialized() },
+            data: unsafe { Box::from_raw(Box::into_raw(data) as *mut [AtomicU16]) },
             logger: log,
         }
     }
@@ -32,43 +41,61 @@ impl NaiveRAM {
 
 
 impl Table for NaiveRAM {
+    fn get(&self, i: usize) -> State {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut State {
+        &mut self[i]
+    }
+
     fn insert(&mut self, i: usize, v: State) {
-        info!(self.logger, "mem access"; "idx" => i);
-        unsafe {
-            ptr::write(&mut self[i], v);
-        }
+        self[i] = v;
     }
 
     fn pre_hook(&mut self, _: usize) {}
     fn post_hook(&mut self, _: usize) {}
     fn finish_hook(&mut self) {}
+    fn flush(&mut self) {}
+}
+
+impl ParTable for NaiveRAM {
+    fn atomic(&self, i: usize) -> &AtomicU16 {
+        info!(self.logger, "mem access"; "idx" => i);
+        &self.data[i]
+    }
 }
 
 impl Index<usize> for NaiveRAM {
     type Output = State;
     fn index(&self, i: usize) -> &State {
         info!(self.logger, "mem access"; "idx" => i);
-        &self.data[i]
+        // every u16 is a valid state, and the workers only go through
+        // `atomic`, so nothing is stored while the reference is alive
+        unsafe { &*(&self.data[i] as *const AtomicU16 as *const State) }
     }
 }
 
 impl IndexMut<usize> for NaiveRAM {
     fn index_mut(&mut self, i: usize) -> &mut State {
         info!(self.logger, "mem access"; "idx" => i);
-        &mut self.data[i]
+        unsafe { &mut *(self.data[i].get_mut() as *mut u16 as *mut State) }
     }
 }
 
 
+/// Table mapped from the file `table_mmap`.
 pub struct MMaped {
     ptr: *mut State,
+    /// number of states
     len: usize,
+    writable: bool,
     logger: Logger,
 }
 
 impl MMaped {
-    pub fn new<T: AsRef<Path>>(wd: T, log: Logger) -> io::Result<Self> {
-        let size = mem::size_of::<State>() * NBOARDS;
+    pub fn new<T: AsRef<Path>>(wd: T, len: usize, log: Logger) -> io::Result<Self> {
+        let size = mem::size_of::<State>() * len;
         let fd = OpenOptions::new()
                    .read(true)
                    .write(true)
@@ -86,14 +113,48 @@ impl MMaped {
             return Err(io::Error::last_os_error());
         } else {
             return Ok(MMaped { ptr: ptr as *mut State,
-                               len: size,
+                               len: len,
+                               writable: true,
+                               logger: log });
+        }
+    }
+
+    /// Map the existing table of `len` states read-only, failing if the file
+    /// has another size.
+    pub fn open<T: AsRef<Path>>(wd: T, len: usize, log: Logger) -> io::Result<Self> {
+        let size = mem::size_of::<State>() * len;
+        let fd = File::open(wd.as_ref().join("table_mmap"))?;
+        if fd.metadata()?.len() != size as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad table size"));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), size as libc::size_t, libc::PROT_READ,
+                       libc::MAP_SHARED, fd.as_raw_fd(), 0)
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        } else {
+            return Ok(MMaped { ptr: ptr as *mut State,
+                               len: len,
+                               writable: false,
                                logger: log });
         }
     }
 }
 
 impl Table for MMaped {
+    fn get(&self, i: usize) -> State {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut State {
+        &mut self[i]
+    }
+
     fn insert(&mut self, i: usize, s: State) {
+        assert!(self.writable, "table not writable");
         unsafe {
             ptr::write(self.ptr.offset(i as isize), s);
         }
@@ -101,7 +162,36 @@ impl Table for MMaped {
 
     fn pre_hook(&mut self, _: usize) {}
     fn post_hook(&mut self, _: usize) {}
+
     fn finish_hook(&mut self) {}
+
+    fn flush(&mut self) {
+        if !self.writable {
+            return;
+        }
+        let r = unsafe {
+            libc::msync(self.ptr as *mut libc::c_void,
+                        self.len * mem::size_of::<State>(), libc::MS_SYNC)
+        };
+        if r != 0 {
+            panic!("msync failed: {}", io::Error::last_os_error());
+        }
+    }
+}
+
+// The table owns its mapping, which is only unmapped on drop. Like the
+// states of `NaiveRAM`, the only writes through a shared reference are the
+// atomic ones of `ParTable::atomic` (the mapping is page-aligned and has the
+// layout of `AtomicU16`), and references to states are only taken when the
+// workers are done.
+unsafe impl Send for MMaped {}
+unsafe impl Sync for MMaped {}
+
+impl ParTable for MMaped {
+    fn atomic(&self, i: usize) -> &AtomicU16 {
+        debug_assert!(i < self.len);
+        unsafe { &*(self.ptr.offset(i as isize) as *const AtomicU16) }
+    }
 }
 
 impl Index<usize> for MMaped {
@@ -116,6 +206,7 @@ impl Index<usize> for MMaped {
 impl IndexMut<usize> for MMaped {
     fn index_mut(&mut self, i: usize) -> &mut State {
         debug_assert!(i < self.len);
+        assert!(self.writable, "table not writable");
         unsafe { &mut *self.ptr.offset(i as isize) }
     }
 }
@@ -123,7 +214,571 @@ impl IndexMut<usize> for MMaped {
 impl Drop for MMaped {
     fn drop(&mut self) {
         unsafe {
-            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+            libc::munmap(self.ptr as *mut libc::c_void, self.len * mem::size_of::<State>());
+        }
+    }
+}
+
+
+/// Page of a `Paged` table.
+struct Page {
+    data: Box<[State]>,
+    dirty: bool,
+    stamp: u64,
+}
+
+
+/// File of a `Paged` table, cut in pages of `page` states.
+struct Pages {
+    file: File,
+    len: usize,
+    page: usize,
+}
+
+impl Pages {
+    /// Byte range of the page `p` in the file.
+    #[inline]
+    fn extent(&self, p: usize) -> (u64, usize) {
+        let sz = mem::size_of::<State>();
+        let n = min(self.page, self.len - p * self.page);
+        ((p * self.page * sz) as u64, n * sz)
+    }
+
+    fn read(&self, p: usize) -> Page {
+        let (off, n) = self.extent(p);
+        let mut data: Box<[State]> = vec![State::unpack(0); self.page].into_boxed_slice();
+        {
+            let buf = unsafe { slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, n) };
+            let mut done = 0;
+            while done < n {
+                match self.file.read_at(&mut buf[done..], off + done as u64) {
+                    Ok(0) => panic!("table file too short"),
+                    Ok(k) => done += k,
+                    Err(e) => panic!("read failed: {}", e),
+                }
+            }
+        }
+        Page { data: data, dirty: false, stamp: 0 }
+    }
+
+    fn write_back(&self, p: usize, page: &mut Page) {
+        if page.dirty {
+            let (off, n) = self.extent(p);
+            let buf = unsafe { slice::from_raw_parts(page.data.as_ptr() as *const u8, n) };
+            let mut done = 0;
+            while done < n {
+                match self.file.write_at(&buf[done..], off + done as u64) {
+                    Ok(k) => done += k,
+                    Err(e) => panic!("write failed: {}", e),
+                }
+            }
+            page.dirty = false;
+        }
+    }
+}
+
+
+/// Pages of a `Paged` table currently in memory.
+struct Cache {
+    pages: HashMap<usize, Page>,
+    /// pages by time of last access
+    lru: BTreeMap<u64, usize>,
+    cap: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    /// Return the page `p`, reading it if needed, and mark it as the most
+    /// recently used. The least recently used pages are written back and
+    /// evicted to make room for it.
+    fn load(&mut self, file: &Pages, p: usize) -> &mut Page {
+        self.clock += 1;
+        if self.pages.contains_key(&p) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            while self.pages.len() >= self.cap {
+                let (&stamp, &q) = self.lru.iter().next().unwrap();
+                self.lru.remove(&stamp);
+                let mut page = self.pages.remove(&q).unwrap();
+                file.write_back(q, &mut page);
+            }
+            self.pages.insert(p, file.read(p));
+        }
+        let page = self.pages.get_mut(&p).unwrap();
+        self.lru.remove(&page.stamp);
+        self.lru.insert(self.clock, p);
+        page.stamp = self.clock;
+        return page;
+    }
+
+    /// Write back every dirty page.
+    fn write_all(&mut self, file: &Pages) {
+        for (&p, page) in self.pages.iter_mut() {
+            file.write_back(p, page);
+        }
+    }
+}
+
+
+/// Table stored in the file `table_paged`, of which at most `cap` pages of
+/// `page` states are kept in memory, the least recently used ones being
+/// written back and evicted first.
+///
+/// Reads copy the states out, so any access may evict a page. Dirty pages are
+/// written back at the end of each layer and when the table is dropped. It
+/// can't be shared between threads.
+pub struct Paged {
+    file: Pages,
+    cache: RefCell<Cache>,
+    logger: Logger,
+}
+
+impl Paged {
+    pub fn new<T: AsRef<Path>>(wd: T, len: usize, page: usize, cap: usize,
+                               log: Logger) -> io::Result<Self> {
+        assert!(page > 0 && cap > 0, "empty cache");
+        let file = OpenOptions::new()
+                     .read(true)
+                     .write(true)
+                     .create(true)
+                     .open(wd.as_ref().join("table_paged"))?;
+        file.set_len((mem::size_of::<State>() * len) as u64)?;
+        Ok(Paged::with_file(file, len, page, cap, log))
+    }
+
+    /// Open the existing table of `len` states read-only, failing if the file
+    /// has another size.
+    pub fn open<T: AsRef<Path>>(wd: T, len: usize, page: usize, cap: usize,
+                                log: Logger) -> io::Result<Self> {
+        assert!(page > 0 && cap > 0, "empty cache");
+        let file = File::open(wd.as_ref().join("table_paged"))?;
+        if file.metadata()?.len() != (mem::size_of::<State>() * len) as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad table size"));
+        }
+        Ok(Paged::with_file(file, len, page, cap, log))
+    }
+
+    fn with_file(file: File, len: usize, page: usize, cap: usize, log: Logger) -> Self {
+        Paged {
+            file: Pages { file: file, len: len, page: page },
+            cache: RefCell::new(Cache { pages: HashMap::new(),
+                                        lru: BTreeMap::new(),
+                                        cap: cap,
+                                        clock: 0,
+                                        hits: 0,
+                                        misses: 0 }),
+            logger: log,
         }
     }
+
+    /// Number of accesses that found their page in memory and that had to
+    /// load it.
+    pub fn stats(&self) -> (u64, u64) {
+        let c = self.cache.borrow();
+        (c.hits, c.misses)
+    }
+}
+
+impl Table for Paged {
+    fn get(&self, i: usize) -> State {
+        debug_assert!(i < self.file.len);
+        let page = self.file.page;
+        self.cache.borrow_mut().load(&self.file, i / page).data[i % page]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut State {
+        debug_assert!(i < self.file.len);
+        let page = self.cache.get_mut().load(&self.file, i / self.file.page);
+        page.dirty = true;
+        &mut page.data[i % self.file.page]
+    }
+
+    fn insert(&mut self, i: usize, v: State) {
+        *self.get_mut(i) = v;
+    }
+
+    fn pre_hook(&mut self, n: usize) {
+        let (hits, misses) = self.stats();
+        info!(self.logger, "layer start"; "layer" => n, "hits" => hits, "misses" => misses);
+    }
+
+    fn post_hook(&mut self, n: usize) {
+        self.cache.get_mut().write_all(&self.file);
+        let (hits, misses) = self.stats();
+        info!(self.logger, "layer end"; "layer" => n, "hits" => hits, "misses" => misses,
+                                        "pages" => self.cache.borrow().pages.len());
+    }
+
+    fn finish_hook(&mut self) {
+        self.flush();
+        let (hits, misses) = self.stats();
+        info!(self.logger, "finish"; "hits" => hits, "misses" => misses);
+    }
+
+    fn flush(&mut self) {
+        self.cache.get_mut().write_all(&self.file);
+        if let Err(e) = self.file.file.sync_data() {
+            panic!("fsync failed: {}", e);
+        }
+    }
+}
+
+impl Drop for Paged {
+    fn drop(&mut self) {
+        self.cache.get_mut().write_all(&self.file);
+    }
+}
+
+
+/// Mapping of the file of one layer of a `Layered` table.
+struct LayerMap {
+    ptr: *mut State,
+    len: usize,
+    writable: bool,
+}
+
+impl LayerMap {
+    fn new(path: &Path, len: usize, writable: bool) -> io::Result<Self> {
+        if len == 0 {
+            return Ok(LayerMap { ptr: ptr::null_mut(), len: 0, writable: writable });
+        }
+        let size = mem::size_of::<State>() * len;
+        let fd = OpenOptions::new()
+                   .read(true)
+                   .write(writable)
+                   .create(writable)
+                   .open(path)?;
+        if writable {
+            fd.set_len(size as u64)?;
+        } else if fd.metadata()?.len() != size as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad layer file size"));
+        }
+        let prot = if writable { libc::PROT_READ | libc::PROT_WRITE } else { libc::PROT_READ };
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), size as libc::size_t, prot,
+                       libc::MAP_SHARED, fd.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(LayerMap { ptr: ptr as *mut State, len: len, writable: writable })
+    }
+
+    fn sync(&self) {
+        if self.len > 0 && self.writable {
+            let r = unsafe {
+                libc::msync(self.ptr as *mut libc::c_void,
+                            self.len * mem::size_of::<State>(), libc::MS_SYNC)
+            };
+            if r != 0 {
+                panic!("msync failed: {}", io::Error::last_os_error());
+            }
+        }
+    }
+}
+
+impl Drop for LayerMap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len * mem::size_of::<State>());
+            }
+        }
+    }
+}
+
+
+/// Table storing each layer in its own file `layer_<n>` in the working
+/// directory.
+///
+/// `pre_hook` maps the layer being solved for writing and the layers before
+/// it for reading, `post_hook` syncs the finished layer and closes every
+/// layer, so the files are only open while a layer is being solved. At the
+/// end of the run, `finish_hook` maps every layer read-only, as does `open`
+/// for the table of a previous run. Only the states of the current layer can
+/// be written, so the layers of a game can't be moved by
+/// `ra::Driver::extend`.
+pub struct Layered {
+    wd: PathBuf,
+    /// start, end and number of every layer, by increasing codes
+    layers: Vec<(usize, usize, usize)>,
+    maps: Vec<Option<LayerMap>>,
+    logger: Logger,
+}
+
+impl Layered {
+    pub fn new<T: AsRef<Path>, G: Game>(wd: T, game: &G, log: Logger) -> Self {
+        let mut layers: Vec<_> = game.layers().into_iter().map(|n| {
+            let (a, b) = game.layer_range(n);
+            (a, b, n)
+        }).collect();
+        layers.sort();
+        let maps = layers.iter().map(|_| None).collect();
+        Layered { wd: wd.as_ref().to_path_buf(), layers: layers, maps: maps, logger: log }
+    }
+
+    /// Open the table of a previous run, mapping every layer that was
+    /// written read-only (the other ones are not accessible until they are
+    /// solved).
+    pub fn open<T: AsRef<Path>, G: Game>(wd: T, game: &G, log: Logger) -> io::Result<Self> {
+        let mut table = Layered::new(wd, game, log);
+        for j in 0..table.layers.len() {
+            let (a, b, n) = table.layers[j];
+            let path = table.path(n);
+            if path.exists() {
+                table.maps[j] = Some(LayerMap::new(&path, b - a, false)?);
+            }
+        }
+        Ok(table)
+    }
+
+    fn path(&self, n: usize) -> PathBuf {
+        self.wd.join(format!("layer_{}", n))
+    }
+
+    /// Position in `layers` of the layer `n`.
+    fn position(&self, n: usize) -> usize {
+        self.layers.iter().position(|l| l.2 == n).expect("unknown layer")
+    }
+
+    /// Mapping and offset of the state `i`.
+    #[inline]
+    fn locate(&self, i: usize) -> (&LayerMap, usize) {
+        let j = layer_of(&self.layers, i);
+        let map = self.maps[j].as_ref().expect("layer not mapped");
+        debug_assert!(i - self.layers[j].0 < map.len);
+        (map, i - self.layers[j].0)
+    }
+
+    /// Map the layer at position `j`.
+    fn map(&mut self, j: usize, writable: bool) {
+        let (a, b, n) = self.layers[j];
+        self.maps[j] = None;
+        let map = LayerMap::new(&self.path(n), b - a, writable).unwrap();
+        self.maps[j] = Some(map);
+    }
+}
+
+/// Position of the layer containing the state `i` in `layers` (sorted by
+/// increasing codes). Empty layers (possible for a `Reachable` game) share
+/// their start with another layer and never contain `i`.
+fn layer_of(layers: &[(usize, usize, usize)], i: usize) -> usize {
+    layers.binary_search_by(|l| {
+        if l.1 <= i {
+            Ordering::Less
+        } else if l.0 > i {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }).expect("state out of the table")
+}
+
+impl Table for Layered {
+    fn get(&self, i: usize) -> State {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut State {
+        &mut self[i]
+    }
+
+    fn insert(&mut self, i: usize, v: State) {
+        unsafe {
+            ptr::write(&mut self[i], v);
+        }
+    }
+
+    fn pre_hook(&mut self, n: usize) {
+        let j = self.position(n);
+        info!(self.logger, "open layers"; "layer" => n);
+        for k in 0..j {
+            if self.maps[k].is_none() {
+                self.map(k, false);
+            }
+        }
+        self.map(j, true);
+        for k in j+1..self.maps.len() {
+            self.maps[k] = None;
+        }
+    }
+
+    fn post_hook(&mut self, n: usize) {
+        let j = self.position(n);
+        if let Some(ref map) = self.maps[j] {
+            map.sync();
+        }
+        for map in self.maps.iter_mut() {
+            *map = None;
+        }
+        info!(self.logger, "close layers"; "layer" => n);
+    }
+
+    fn finish_hook(&mut self) {
+        for j in 0..self.layers.len() {
+            self.map(j, false);
+        }
+    }
+
+    fn flush(&mut self) {
+        for map in self.maps.iter().filter_map(|m| m.as_ref()) {
+            map.sync();
+        }
+    }
+}
+
+// Same as for `MMaped`: the table owns its mappings, which are only changed
+// by the hooks (through `&mut self`), and the workers only go through
+// `ParTable::atomic`.
+unsafe impl Send for Layered {}
+unsafe impl Sync for Layered {}
+
+impl ParTable for Layered {
+    fn atomic(&self, i: usize) -> &AtomicU16 {
+        let (map, o) = self.locate(i);
+        unsafe { &*(map.ptr.offset(o as isize) as *const AtomicU16) }
+    }
+}
+
+impl Index<usize> for Layered {
+    type Output = State;
+
+    fn index(&self, i: usize) -> &State {
+        let (map, o) = self.locate(i);
+        unsafe { &*map.ptr.offset(o as isize) }
+    }
+}
+
+impl IndexMut<usize> for Layered {
+    fn index_mut(&mut self, i: usize) -> &mut State {
+        let (map, o) = self.locate(i);
+        assert!(map.writable, "layer not writable");
+        unsafe { &mut *map.ptr.offset(o as isize) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{create_dir_all,remove_dir_all,read_dir};
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use rules::{AllButLast,Forbidden};
+    use ra::{Table,Solver,solve};
+    use par::ParDriver;
+    use reach::{explore,Reachable};
+    use super::{NaiveRAM,MMaped,Paged,Layered,layer_of};
+
+    #[test]
+    fn paged_same_as_ram() {
+        let wd = env::temp_dir().join("awari-paged-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 10), rules: AllButLast };
+
+        let ram = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        let mut paged = solve(game, Paged::new(&wd, game.len(), 100, 8, log.clone()).unwrap());
+        let (hits, misses) = paged.stats();
+        assert!(hits > 0 && misses > 0);
+        // reads evict pages as well
+        assert!((0..game.len()).all(|c| ram[c] == paged.get(c)));
+        assert!(paged.cache.borrow().pages.len() <= 8);
+
+        // everything is written back when dropped
+        let mut s = paged.get(42);
+        s.val += 1;
+        paged.insert(42, s);
+        drop(paged);
+        assert!(Paged::open(&wd, game.len() + 1, 1000, 1, log.clone()).is_err());
+        let paged = Paged::open(&wd, game.len(), 1000, 1, log).unwrap();
+        assert!((0..game.len()).all(|c| c == 42 || ram[c] == paged.get(c)));
+        assert!(paged.get(42) == s);
+        remove_dir_all(&wd).unwrap();
+    }
+
+    #[test]
+    fn mmaped_open() {
+        let wd = env::temp_dir().join("awari-mmaped-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 8), rules: AllButLast };
+
+        let ram = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        drop(solve(game, MMaped::new(&wd, game.len(), log.clone()).unwrap()));
+        assert!(MMaped::open(&wd, game.len() + 1, log.clone()).is_err());
+        let table = MMaped::open(&wd, game.len(), log).unwrap();
+        assert!((0..game.len()).all(|c| ram[c] == table[c]));
+        remove_dir_all(&wd).unwrap();
+    }
+
+    #[test]
+    fn layered_same_as_ram() {
+        let wd = env::temp_dir().join("awari-layered-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 10), rules: AllButLast };
+
+        let ram = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        let layered = solve(game, Layered::new(&wd, &game, log.clone()));
+        assert!((0..game.len()).all(|c| ram[c] == layered[c]));
+        assert_eq!(read_dir(&wd).unwrap().count(), game.layers().len());
+        drop(layered);
+        let mut layered = Layered::open(&wd, &game, log.clone()).unwrap();
+        assert!((0..game.len()).all(|c| ram[c] == layered[c]));
+        // the layers are closed once one is solved
+        let n = game.layers()[0];
+        layered.pre_hook(n);
+        layered.post_hook(n);
+        assert!(layered.maps.iter().all(|m| m.is_none()));
+        drop(layered);
+
+        let mut par = ParDriver { game: game,
+                                  table: Layered::new(&wd, &game, log.clone()),
+                                  logger: log,
+                                  threads: 3,
+                                  checkpoint: None };
+        par.run();
+        assert!((0..game.len()).all(|c| ram[c] == par.table[c]));
+        remove_dir_all(&wd).unwrap();
+    }
+
+    #[test]
+    fn layer_of_empty_layers() {
+        let layers = [(0, 0, 0), (0, 0, 1), (0, 0, 2), (0, 2, 3), (2, 2, 4), (2, 2, 5),
+                      (2, 2, 6), (2, 5, 7), (5, 5, 8)];
+        for i in 0..5 {
+            let j = layer_of(&layers, i);
+            assert!(layers[j].0 <= i && i < layers[j].1);
+        }
+    }
+
+    #[test]
+    fn layered_empty_layers() {
+        let wd = env::temp_dir().join("awari-layered-empty-test");
+        let _ = remove_dir_all(&wd);
+        create_dir_all(&wd).unwrap();
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(2, 10), rules: Forbidden };
+        let set = explore(&game);
+        let reach = Reachable::new(game, &set);
+        assert!(reach.layers().into_iter().any(|n| {
+            let (a, b) = reach.layer_range(n);
+            a == b && a > 0
+        }));
+
+        let ram = solve(reach, NaiveRAM::new(reach.len(), log.clone()));
+        let layered = solve(reach, Layered::new(&wd, &reach, log));
+        assert!((0..reach.len()).all(|c| ram[c] == layered[c]));
+        remove_dir_all(&wd).unwrap();
+    }
 }
@@ -1,19 +1,104 @@
-use {PITS,FPITS,SEEDS,BINOM_TBL};
+use {MAX_PITS,MAX_FPITS,MAX_SEEDS};
+
+
+lazy_static! {
+    /// Precomputed binomial coefficients, large enough for every supported
+    /// geometry.
+    static ref BINOM_TBL: Vec<usize> = {
+        let mut tbl = vec![0; MAX_FPITS * (MAX_FPITS + MAX_SEEDS + 1)];
+        for n in 1..MAX_FPITS+MAX_SEEDS+1 {
+            for k in 0..MAX_FPITS {
+                tbl[n*MAX_FPITS + k] = binom_slow(k + 1, n);
+            }
+        }
+        tbl
+    };
+}
+
+
+fn binom_slow(k: usize, n: usize) -> usize {
+    if n < k {
+        return 0;
+    }
+    let mut p = 1;
+    for i in 0..k {
+        p *= n - i;
+        p /= i + 1;
+    }
+    return p;
+}
+
+
+/// Shape of the game: number of pits per player and total number of seeds.
+///
+/// Unless `full` is set, the boards with SEEDS-1 seeds (which are not
+/// accessible from the starting board) are left out of the encoding.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Geometry {
+    pub pits: usize,
+    pub seeds: usize,
+    pub full: bool,
+}
+
+impl Geometry {
+    pub fn new(pits: usize, seeds: usize) -> Self {
+        assert!(0 < pits && pits <= MAX_PITS, "unsupported number of pits");
+        assert!(1 < seeds && seeds <= MAX_SEEDS, "unsupported number of seeds");
+        Geometry { pits: pits, seeds: seeds, full: false }
+    }
+
+    /// Same as `new` but encode every board with at most `seeds` seeds.
+    pub fn new_full(pits: usize, seeds: usize) -> Self {
+        Geometry { full: true, ..Geometry::new(pits, seeds) }
+    }
+
+    /// Test if the boards with `n` seeds are left out of the encoding.
+    #[inline]
+    pub fn skips(&self, n: usize) -> bool {
+        !self.full && n + 1 == self.seeds
+    }
+
+    /// Total number of pits on the board.
+    #[inline]
+    pub fn fpits(&self) -> usize {
+        2 * self.pits
+    }
+
+    /// Number of seeds in each pit of the starting board.
+    #[inline]
+    pub fn start_seeds(&self) -> usize {
+        self.seeds / self.fpits()
+    }
+
+    /// Number of encoded boards, that is the size of the score table.
+    pub fn nboards(&self) -> usize {
+        enc_min(*self, self.seeds) + n_boards(*self, self.seeds)
+    }
+
+    /// Range of the codes of the boards with `n` seeds.
+    pub fn layer_range(&self, n: usize) -> (usize, usize) {
+        if n == 0 {
+            return (0, 1);
+        }
+        let a = enc_min(*self, n);
+        (a, a + n_boards(*self, n))
+    }
+}
 
 
 /// Compute a binomial coefficient (using in-memory precomputed table).
 #[inline]
 pub fn binom(k: usize, n: usize) -> usize {
-    debug_assert!(0 < k && k <= FPITS && n <= FPITS+SEEDS);
-    BINOM_TBL[n*FPITS + k - 1]
+    debug_assert!(0 < k && k <= MAX_FPITS && n <= MAX_FPITS+MAX_SEEDS);
+    BINOM_TBL[n*MAX_FPITS + k - 1]
 }
 
 
 /// Compute the largest ``n`` such that ``\binom{n}{k} <= x``.
 #[inline]
-pub fn binom_maxinv(k : usize , x: usize ) -> (usize , usize ) {
+pub fn binom_maxinv(geo: Geometry, k : usize , x: usize ) -> (usize , usize ) {
     debug_assert!(k != 0);
-    let (mut a, mut b) = (k-1, FPITS+SEEDS+1);
+    let (mut a, mut b) = (k-1, geo.fpits()+geo.seeds+1);
 
     while b - a > 1 {
         let c = (a + b + 1) / 2;
@@ -26,14 +111,16 @@ pub fn binom_maxinv(k : usize , x: usize ) -> (usize , usize ) {
     return (a, binom(k, a));
 }
 
-pub fn n_boards(n: usize) -> usize {
-    binom(FPITS-1, FPITS+n-1) - binom(FPITS-1, PITS+n-1)
+pub fn n_boards(geo: Geometry, n: usize) -> usize {
+    let (pits, fpits) = (geo.pits, geo.fpits());
+    binom(fpits-1, fpits+n-1) - binom(fpits-1, pits+n-1)
 }
 
-pub fn enc_min(n: usize) -> usize {
-    let mut a = binom(FPITS, FPITS + n - 1) - binom(FPITS, PITS + n - 1);
-    if n == SEEDS {
-        a -= binom(FPITS - 1, FPITS + SEEDS - 2) - binom(FPITS-1, PITS + SEEDS - 2);
+pub fn enc_min(geo: Geometry, n: usize) -> usize {
+    let (pits, fpits, seeds) = (geo.pits, geo.fpits(), geo.seeds);
+    let mut a = binom(fpits, fpits + n - 1) - binom(fpits, pits + n - 1);
+    if n == seeds && !geo.full {
+        a -= binom(fpits - 1, fpits + seeds - 2) - binom(fpits-1, pits + seeds - 2);
     }
     return a;
 }
@@ -51,17 +138,19 @@ pub fn divmod(n: u8, d: u8) -> (u8, usize) {
 mod tests {
     use super::*;
 
+    const GEO: Geometry = Geometry { pits: 6, seeds: 24, full: false };
+
     #[quickcheck]
     fn binom_rel1(k: usize, n: usize) -> bool {
         let (k, n) = (k >> 59, n >> 59);
         k == 0 || k >= n || binom(k, n) == binom(k-1, n-1) + binom(k, n-1)
     }
-    
+
     #[quickcheck]
     fn binom_maxinv_rel1(k: usize, x: usize) -> bool {
         let (k, x) = (k >> 59, x >> 58);
         if k == 0 { return true; }
-        let (n, b) = binom_maxinv(k, x);
+        let (n, b) = binom_maxinv(GEO, k, x);
         return b == binom(k, n);
     }
 
@@ -69,7 +158,7 @@ mod tests {
     fn binom_maxinv_rel2(k: usize, x: usize) -> bool {
         let (k, x) = (k >> 59, x >> 58);
         if k == 0 { return true; }
-        let (_, b) = binom_maxinv(k, x);
+        let (_, b) = binom_maxinv(GEO, k, x);
         return b <= x;
     }
 }
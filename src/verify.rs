@@ -0,0 +1,92 @@
+use std::cmp::max;
+use std::fmt::Debug;
+
+use slog::Logger;
+
+use game::Game;
+use ra::Scores;
+
+
+/// Score of `u` given by its successors in `table` (one-ply Bellman identity),
+/// or its terminal score if it has no successor.
+pub fn bellman<G: Game, T: Scores>(game: &G, table: &T, u: &G::State) -> i8 {
+    let mut best = None;
+    for (_, k, c, again, _) in game.successors(u) {
+        let y = table.state(c).val;
+        let x = if again { k as i8 + y } else { k as i8 - y };
+        best = Some(best.map_or(x, |b| max(b, x)));
+    }
+    best.unwrap_or_else(|| game.terminal_score(u))
+}
+
+
+/// Check a finished score table against the rules of the game, independently
+/// of the driver that computed it.
+///
+/// Every state must be stable and its score must satisfy the Bellman
+/// identity. Cyclic states, which the driver set to 0, satisfy it as well:
+/// they have a 0-valued successor (staying in the cycle) and no better one.
+/// Returns the codes and states of the mismatches, which are also logged.
+pub fn verify<G, T>(game: &G, table: &T, log: &Logger) -> Vec<(usize, G::State)>
+        where G: Game, G::State: Debug, T: Scores {
+    let mut bad = Vec::new();
+    for n in game.layers() {
+        info!(log, "verify layer"; "layer" => n);
+        for (c, u) in game.iter_layer(n) {
+            let s = table.state(c);
+            let want = bellman(game, table, &u);
+            if s.nsuc.is_some() || s.val != want {
+                warn!(log, "mismatch"; "code" => c, "score" => s.val, "bellman" => want,
+                                       "stable" => s.nsuc.is_none(),
+                                       "board" => format!("{:?}", u));
+                bad.push((c, u));
+            }
+        }
+    }
+    info!(log, "verify end"; "mismatches" => bad.len());
+    return bad;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use kalah::KalahGame;
+    use rules::{Variant,Ending,Forbidden,CaptureAll};
+    use ra::{NaiveRAM,solve};
+    use super::verify;
+
+    #[test]
+    fn verify_solved() {
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 10),
+                               rules: Variant { slam: CaptureAll, ending: Ending::Split } };
+        let table = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        assert!(verify(&game, &table, &log).is_empty());
+
+        let game = AwariGame { geo: Geometry::new_full(3, 9), rules: Forbidden };
+        let table = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        assert!(verify(&game, &table, &log).is_empty());
+
+        let game = KalahGame { geo: Geometry::new(3, 8) };
+        let table = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        assert!(verify(&game, &table, &log).is_empty());
+    }
+
+    #[test]
+    fn verify_corrupted() {
+        let log = Logger::root(Discard, o!());
+        let game = AwariGame { geo: Geometry::new(3, 6), rules: Forbidden };
+        let mut table = solve(game, NaiveRAM::new(game.len(), log.clone()));
+        let c = game.layer_range(6).0 + 1;
+        table[c].val += 1;
+        let bad: Vec<usize> = verify(&game, &table, &log).into_iter()
+                                                         .map(|(d, _)| d)
+                                                         .collect();
+        assert!(bad.contains(&c));
+    }
+}
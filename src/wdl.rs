@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::convert::AsRef;
+use std::fs::File;
+use std::io::{self,BufReader,BufWriter};
+
+use slog::Logger;
+
+use game::Game;
+use packed::PackedVec;
+use ra::{Scores,Driver,NaiveRAM,Solver};
+
+
+/// Outcome of the rest of the game for the player to move, that is the sign of
+/// the optimal score of the board.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Wdl {
+    Loss = 0,
+    Draw = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    pub fn of_score(x: i8) -> Self {
+        if x > 0 {
+            Wdl::Win
+        } else if x < 0 {
+            Wdl::Loss
+        } else {
+            Wdl::Draw
+        }
+    }
+}
+
+
+/// Win/draw/loss table, 2 bits per board, indexed by the codes of the boards.
+pub struct WdlTable {
+    data: PackedVec,
+}
+
+impl WdlTable {
+    /// Derive the table from the first `len` entries of a finished score table.
+    pub fn from_scores<T: Scores>(table: &T, len: usize) -> Self {
+        let mut data = PackedVec::new(2, len);
+        for i in 0..len {
+            data.set(i, Wdl::of_score(table.state(i).val) as u8);
+        }
+        WdlTable { data: data }
+    }
+
+    /// Solve the game in memory and only keep the win/draw/loss table.
+    pub fn compute<G: Game>(game: G, log: Logger) -> Self {
+        let mut driver = Driver::new(game, NaiveRAM::new(game.len(), log.new(o!())), log);
+        driver.run();
+        WdlTable::from_scores(&driver.table, game.len())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Outcome of the board whose code is `code`.
+    #[inline]
+    pub fn get(&self, code: usize) -> Wdl {
+        match self.data.get(code) {
+            0 => Wdl::Loss,
+            1 => Wdl::Draw,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// Outcome of the state `u` of `game`.
+    #[inline]
+    pub fn lookup<G: Game>(&self, game: &G, u: &G::State) -> Wdl {
+        self.get(game.encode(u))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.data.write_to(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = PackedVec::read_from(&mut BufReader::new(File::open(path)?))?;
+        if data.bits() != 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a wdl table"));
+        }
+        Ok(WdlTable { data: data })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use slog::{Logger,Discard};
+
+    use utils::Geometry;
+    use game::Game;
+    use awari::AwariGame;
+    use rules::Forbidden;
+    use ra::{NaiveRAM,solve};
+    use super::{Wdl,WdlTable};
+
+    #[test]
+    fn wdl_sign_of_scores() {
+        let game = AwariGame { geo: Geometry::new(3, 10), rules: Forbidden };
+        let log = Logger::root(Discard, o!());
+        let table = solve(game, NaiveRAM::new(game.len(), log.clone()));
+
+        let wdl = WdlTable::compute(game, log);
+        assert_eq!(wdl.len(), game.len());
+        for c in 0..game.len() {
+            assert_eq!(wdl.get(c), Wdl::of_score(table[c].val));
+        }
+    }
+
+    #[test]
+    fn wdl_terminal_boards() {
+        let game = AwariGame { geo: Geometry::new(3, 10), rules: Forbidden };
+        let wdl = WdlTable::compute(game, Logger::root(Discard, o!()));
+        assert_eq!(wdl.get(0), Wdl::Draw);
+
+        // the boards without moves have their terminal score, and a move to
+        // a lost one of them wins
+        let lost = |v: &_| game.successors(v).next().is_none() && game.terminal_score(v) < 0;
+        let mut seen = [false; 3];
+        for n in game.layers() {
+            for (c, u) in game.iter_layer(n) {
+                let x = if game.successors(&u).next().is_none() {
+                    Wdl::of_score(game.terminal_score(&u))
+                } else if game.successors(&u).any(|(v, _, _, _, _)| lost(&v)) {
+                    Wdl::Win
+                } else {
+                    continue;
+                };
+                assert_eq!(wdl.lookup(&game, &u), x);
+                assert_eq!(wdl.get(c), x);
+                seen[x as usize] = true;
+            }
+        }
+        assert_eq!(seen, [true, true, true]);
+    }
+}